@@ -0,0 +1,164 @@
+use std::f32::consts::PI;
+
+use crate::{
+    common::{Float, Float2d},
+    entity::{EntityEvent, Transform},
+    pathfinding::{Pathfinder, Tile},
+    ray::{cast, has_line_of_sight},
+    walls::Walls,
+};
+
+const SIGHT_RANGE: Float = 12.0;
+// enemies notice the player within this angle from their view direction
+const VIEW_ANGLE: Float = 0.66 * PI;
+const ATTACK_RANGE: Float = 1.2;
+const ATTACK_DAMAGE: i32 = 10;
+// seconds between attacks
+const ATTACK_COOLDOWN: Float = 1.0;
+// tiles per second
+const MOVEMENT_SPEED: Float = 2.0;
+// seconds between path updates while chasing
+const REPATH_INTERVAL: Float = 0.5;
+const RADIUS: Float = 0.3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AiState {
+    #[default]
+    Idle,
+    Chase,
+    Attack,
+}
+
+/// Data shared by all enemies during the update
+pub struct AiContext<'a> {
+    pub walls: &'a Walls,
+    pub pathfinder: &'a mut Pathfinder,
+    pub player_pos: Float2d,
+    pub delta_time: Float,
+}
+
+#[derive(Default)]
+pub struct EnemyAi {
+    state: AiState,
+    /// where the player was seen for the last time
+    target: Float2d,
+    path: Vec<Tile>,
+    repath_in: Float,
+    cooldown: Float,
+}
+
+impl EnemyAi {
+    pub fn state(&self) -> AiState {
+        self.state
+    }
+
+    /// Starts chasing towards the `target` point
+    pub fn alert(&mut self, target: Float2d) {
+        self.target = target;
+        if self.state == AiState::Idle {
+            self.state = AiState::Chase;
+            self.repath_in = 0.0;
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        transform: &mut Transform,
+        context: &mut AiContext,
+    ) -> Option<EntityEvent> {
+        let delta_time = context.delta_time;
+        self.cooldown = (self.cooldown - delta_time).max(0.0);
+        self.repath_in -= delta_time;
+        let position = transform.position;
+        let Float2d { x, y } = context.player_pos - position;
+        let distance = x.hypot(y);
+        let direction = y.atan2(x);
+        let can_see = distance < SIGHT_RANGE
+            && has_line_of_sight(context.walls, position, context.player_pos);
+        if can_see {
+            self.target = context.player_pos;
+        }
+        match self.state {
+            AiState::Idle => {
+                let angle_diff = (direction - transform.angle + PI).rem_euclid(2.0 * PI) - PI;
+                if can_see && angle_diff.abs() < 0.5 * VIEW_ANGLE {
+                    self.state = AiState::Chase;
+                    self.repath_in = 0.0;
+                }
+                None
+            }
+            AiState::Chase => {
+                if can_see && distance < ATTACK_RANGE {
+                    self.state = AiState::Attack;
+                    return None;
+                }
+                if self.repath_in <= 0.0 {
+                    self.update_path(position, context);
+                }
+                if !self.follow_path(transform, context) && !can_see {
+                    // reached the last known position and lost the player
+                    self.state = AiState::Idle;
+                }
+                None
+            }
+            AiState::Attack => {
+                transform.angle = direction;
+                if !can_see || distance > ATTACK_RANGE {
+                    self.state = AiState::Chase;
+                    self.repath_in = 0.0;
+                    return None;
+                }
+                // masked walls like windows may stop shots while the player is visible
+                if self.cooldown > 0.0
+                    || cast(context.walls, position, direction, distance).is_some()
+                {
+                    return None;
+                }
+                self.cooldown = ATTACK_COOLDOWN;
+                Some(EntityEvent::PlayerHit {
+                    damage: ATTACK_DAMAGE,
+                })
+            }
+        }
+    }
+
+    fn update_path(&mut self, position: Float2d, context: &mut AiContext) {
+        let start = (position.x.floor() as i32, position.y.floor() as i32);
+        let goal = (self.target.x.floor() as i32, self.target.y.floor() as i32);
+        // budget exhausted, try again on the next frame
+        let Some(path) = context.pathfinder.find(context.walls, start, goal) else {
+            return;
+        };
+        self.path = path.unwrap_or_default();
+        self.repath_in = REPATH_INTERVAL;
+    }
+
+    /// Moves towards the next tile of the path, returns false if there is nowhere to go
+    fn follow_path(&mut self, transform: &mut Transform, context: &AiContext) -> bool {
+        let position = transform.position;
+        let waypoint = match self.path.first() {
+            // last tile is approached directly to stop next to the player
+            Some(_) if self.path.len() == 1 => self.target,
+            Some((col, row)) => Float2d::new(*col as Float + 0.5, *row as Float + 0.5),
+            None => return false,
+        };
+        let Float2d { x, y } = waypoint - position;
+        let distance = x.hypot(y);
+        let step = MOVEMENT_SPEED * context.delta_time;
+        if distance <= step.max(0.05) {
+            self.path.remove(0);
+            return !self.path.is_empty();
+        }
+        transform.angle = y.atan2(x);
+        let offset = Float2d::new(x / distance * step, y / distance * step);
+        for offset in [Float2d::new(offset.x, 0.0), Float2d::new(0.0, offset.y)] {
+            let next = transform.position + offset;
+            let Float2d { x, y } = context.player_pos - next;
+            let is_free = !context.walls.has_collision(next) && x.hypot(y) > ATTACK_RANGE - RADIUS;
+            if is_free {
+                transform.position = next;
+            }
+        }
+        true
+    }
+}
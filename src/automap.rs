@@ -0,0 +1,216 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    common::{DrawCommand, Float, Float2d, ScreenSize},
+    control::ControllerState,
+    minimap::{draw_player_marker, MapTransform},
+    player::Player,
+    ray::GridRay,
+    walls::Walls,
+};
+
+const DEFAULT_TILE_SIZE: Float = 16.0;
+const MIN_TILE_SIZE: Float = 4.0;
+const MAX_TILE_SIZE: Float = 96.0;
+// zoom factor change per second
+const ZOOM_SPEED: Float = 2.0;
+// tiles per second
+const PAN_SPEED: Float = 20.0;
+
+/// Colored square drawn on top of the map, e.g. hazards, doors and items
+pub struct MapMarker {
+    pub position: Float2d,
+    /// in tiles
+    pub size: Float,
+    pub color: (u8, u8, u8),
+}
+
+/// Map tiles the player has seen
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ExploredCells {
+    cols: usize,
+    rows: usize,
+    cells: Vec<bool>,
+}
+
+impl ExploredCells {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            cols,
+            rows,
+            cells: vec![false; cols * rows],
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    pub fn is_explored(&self, col: usize, row: usize) -> bool {
+        col < self.cols && row < self.rows && self.cells[row * self.cols + col]
+    }
+
+    fn mark(&mut self, col: i32, row: i32) {
+        if col < 0 || row < 0 || col as usize >= self.cols || row as usize >= self.rows {
+            return;
+        }
+        self.cells[row as usize * self.cols + col as usize] = true;
+    }
+
+    /// Marks tiles passed by rays from `origin` to each of the `hits` including the hit tiles
+    pub fn reveal(&mut self, origin: Float2d, hits: impl Iterator<Item = Float2d>) {
+        for hit in hits {
+            let Float2d { x, y } = hit - origin;
+            let depth = x.hypot(y);
+            let ray = GridRay::new(origin, y.atan2(x));
+            let (col, row) = ray.origin_tile();
+            self.mark(col, row);
+            // a ray can't cross more tiles than that
+            let max_steps = self.cols + self.rows + 2;
+            for step in ray.take(max_steps) {
+                if step.depth > depth + 1e-3 {
+                    break;
+                }
+                self.mark(step.col, step.row);
+            }
+        }
+    }
+}
+
+/// Full-screen map which shows explored tiles only
+pub struct Automap {
+    tile_size: Float,
+    center: Float2d,
+}
+
+impl Default for Automap {
+    fn default() -> Self {
+        Self {
+            tile_size: DEFAULT_TILE_SIZE,
+            center: Float2d::default(),
+        }
+    }
+}
+
+impl Automap {
+    pub fn update(
+        &mut self,
+        delta_time: Float,
+        controller_state: &ControllerState,
+        player: &Player,
+    ) {
+        if !controller_state.automap_visible || !controller_state.automap_pan_mode {
+            self.center = player.pos();
+        }
+        if !controller_state.automap_visible {
+            return;
+        }
+        let factor = 1.0 + ZOOM_SPEED * delta_time;
+        if controller_state.zoom_in_pressed {
+            self.tile_size *= factor;
+        }
+        if controller_state.zoom_out_pressed {
+            self.tile_size /= factor;
+        }
+        self.tile_size = self.tile_size.clamp(MIN_TILE_SIZE, MAX_TILE_SIZE);
+        if !controller_state.automap_pan_mode {
+            return;
+        }
+        let dist = PAN_SPEED * delta_time;
+        let mut offset = Float2d::default();
+        if controller_state.forward_pressed {
+            offset.y -= dist;
+        }
+        if controller_state.backward_pressed {
+            offset.y += dist;
+        }
+        if controller_state.left_pressed || controller_state.rotate_left_pressed {
+            offset.x -= dist;
+        }
+        if controller_state.right_pressed || controller_state.rotate_right_pressed {
+            offset.x += dist;
+        }
+        self.center += offset;
+    }
+
+    pub fn draw(
+        &self,
+        commands: &mut Vec<DrawCommand>,
+        screen_size: ScreenSize,
+        walls: &Walls,
+        explored: &ExploredCells,
+        markers: &[MapMarker],
+        player: &Player,
+    ) {
+        let ScreenSize { width, height } = screen_size;
+        commands.push(DrawCommand::ColorRGB(10, 10, 20));
+        commands.push(DrawCommand::Rectangle {
+            x: 0,
+            y: 0,
+            w: width,
+            h: height,
+            fill: true,
+        });
+        let origin = Float2d::new(0.5 * width as Float, 0.5 * height as Float);
+        let transform = MapTransform::new(self.center, 0.0, self.tile_size, origin);
+        // visible tiles range
+        let (cols, rows) = walls.size();
+        let half_cols = (origin.x / self.tile_size).ceil() as i32 + 1;
+        let half_rows = (origin.y / self.tile_size).ceil() as i32 + 1;
+        let (center_col, center_row) = (self.center.x as i32, self.center.y as i32);
+        let col_range = (center_col - half_cols).max(0)..(center_col + half_cols).min(cols as i32);
+        let row_range = (center_row - half_rows).max(0)..(center_row + half_rows).min(rows as i32);
+        for row in row_range {
+            for col in col_range.clone() {
+                let (col, row) = (col as usize, row as usize);
+                if !explored.is_explored(col, row) {
+                    continue;
+                }
+                let segment = walls.segment(col as i32, row as i32);
+                let color = if walls.tile(col, row) != 0 && segment.is_none() {
+                    DrawCommand::ColorRGB(220, 220, 220)
+                } else {
+                    DrawCommand::ColorRGB(50, 50, 60)
+                };
+                commands.push(color);
+                let (x1, y1) = transform.apply(Float2d::new(col as Float, row as Float));
+                let (x2, y2) =
+                    transform.apply(Float2d::new(col as Float + 1.0, row as Float + 1.0));
+                commands.push(DrawCommand::Rectangle {
+                    x: x1,
+                    y: y1,
+                    w: (x2 - x1) as u32,
+                    h: (y2 - y1) as u32,
+                    fill: true,
+                });
+                // thin walls are drawn over the floor
+                if let Some((start, end)) = segment {
+                    let (x1, y1) = transform.apply(start);
+                    let (x2, y2) = transform.apply(end);
+                    commands.push(DrawCommand::ColorRGB(220, 220, 220));
+                    commands.push(DrawCommand::Line { x1, y1, x2, y2 });
+                }
+            }
+        }
+        // markers are shown once their tiles are explored
+        for marker in markers {
+            let Float2d { x, y } = marker.position;
+            if !explored.is_explored(x as usize, y as usize) {
+                continue;
+            }
+            let half = 0.5 * marker.size;
+            let (x1, y1) = transform.apply(Float2d::new(x - half, y - half));
+            let (x2, y2) = transform.apply(Float2d::new(x + half, y + half));
+            let (r, g, b) = marker.color;
+            commands.push(DrawCommand::ColorRGB(r, g, b));
+            commands.push(DrawCommand::Rectangle {
+                x: x1,
+                y: y1,
+                w: (x2 - x1).max(2) as u32,
+                h: (y2 - y1).max(2) as u32,
+                fill: true,
+            });
+        }
+        draw_player_marker(commands, player, &transform);
+    }
+}
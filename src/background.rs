@@ -17,6 +17,10 @@ impl Background {
         }
     }
 
+    pub fn resize(&mut self, scene_size: ScreenSize) {
+        self.scene_size = scene_size;
+    }
+
     pub fn update(&mut self, angle: Float) {
         let w = self.scene_size.width as Float;
         self.offset = 1.5 * angle * w / PI;
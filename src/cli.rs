@@ -0,0 +1,233 @@
+use std::path::PathBuf;
+
+use crate::settings::{parse_angle, SettingsSource};
+
+const DEFAULT_SETTINGS_FILE_PATH: &str = "raycaster.toml";
+const DEFAULT_BENCH_FRAMES: usize = 500;
+
+pub const USAGE: &str = "\
+Usage: raycaster [COMMAND] [OPTIONS]
+
+Commands:
+  run                     start the game (default)
+  validate                check settings and the level map, then exit
+  screenshot <file.bmp>   render a single frame to the file
+  bench                   render frames as fast as possible and report timings
+
+Options:
+  --config <path>         settings file (default: raycaster.toml)
+  --level <path>          level map file
+  --spawn <x,y[,angle]>   player position and angle (radians or like 90deg)
+  --windowed              start in a window
+  --fullscreen            start in fullscreen mode
+  --fps <n>               target frames per second
+  --frames <n>            number of frames to render in bench mode (default: 500)
+  --set <key=value>       override any setting, e.g. --set scene.fov=90deg
+  -h, --help              print this help";
+
+pub enum Command {
+    Run,
+    Validate,
+    Screenshot { path: PathBuf },
+    Bench { frames: usize },
+    Help,
+}
+
+pub struct Cli {
+    pub command: Command,
+    pub settings: SettingsSource,
+}
+
+impl Cli {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut command_name = None;
+        let mut is_help = false;
+        let mut config = PathBuf::from(DEFAULT_SETTINGS_FILE_PATH);
+        let mut overrides = Vec::new();
+        let mut frames = DEFAULT_BENCH_FRAMES;
+        let mut screenshot_path = None;
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("missing value for '{name}'"))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => is_help = true,
+                "--config" => config = PathBuf::from(value(&arg)?),
+                "--level" => {
+                    let path = toml::Value::String(value(&arg)?);
+                    overrides.push(("level.map".to_string(), path.to_string()));
+                }
+                "--spawn" => overrides.extend(Self::parse_spawn(&value(&arg)?)?),
+                "--windowed" => {
+                    overrides.push(("scene.fullscreen".to_string(), "false".to_string()))
+                }
+                "--fullscreen" => {
+                    overrides.push(("scene.fullscreen".to_string(), "true".to_string()))
+                }
+                "--fps" => overrides.push(("scene.fps".to_string(), value(&arg)?)),
+                "--frames" => {
+                    let raw = value(&arg)?;
+                    frames = raw
+                        .parse()
+                        .map_err(|_| format!("invalid number of frames '{raw}'"))?;
+                }
+                "--set" => {
+                    let raw = value(&arg)?;
+                    let Some((key, value)) = raw.split_once('=') else {
+                        return Err(format!("expected key=value, got '{raw}'"));
+                    };
+                    overrides.push((key.trim().to_string(), value.trim().to_string()));
+                }
+                "run" | "validate" | "screenshot" | "bench" if command_name.is_none() => {
+                    if arg == "screenshot" {
+                        screenshot_path = Some(PathBuf::from(value(&arg)?));
+                    }
+                    command_name = Some(arg.clone());
+                }
+                _ => return Err(format!("unexpected argument '{arg}'\n\n{USAGE}")),
+            }
+        }
+        // options may follow the command, so it's built when all arguments are read
+        let command = match command_name.as_deref() {
+            _ if is_help => Command::Help,
+            Some("validate") => Command::Validate,
+            Some("screenshot") => Command::Screenshot {
+                path: screenshot_path.unwrap_or_default(),
+            },
+            Some("bench") => Command::Bench { frames },
+            _ => Command::Run,
+        };
+        Ok(Self {
+            command,
+            settings: SettingsSource { config, overrides },
+        })
+    }
+
+    fn parse_spawn(s: &str) -> Result<Vec<(String, String)>, String> {
+        let parts = s.split(',').map(|x| x.trim()).collect::<Vec<_>>();
+        let (x, y, angle) = match parts.as_slice() {
+            [x, y] => (x, y, None),
+            [x, y, angle] => (x, y, Some(angle)),
+            _ => return Err(format!("expected spawn as x,y[,angle], got '{s}'")),
+        };
+        let mut overrides = Vec::new();
+        for (key, value) in [("level.player_x", x), ("level.player_y", y)] {
+            let value = value
+                .parse::<f32>()
+                .map_err(|_| format!("invalid spawn coordinate '{value}'"))?;
+            overrides.push((key.to_string(), format!("{value:?}")));
+        }
+        if let Some(angle) = angle {
+            let angle = parse_angle(angle)?;
+            overrides.push(("level.player_angle".to_string(), format!("{angle:?}")));
+        }
+        Ok(overrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, String> {
+        Cli::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn overrides(cli: &Cli) -> Vec<(&str, &str)> {
+        cli.settings
+            .overrides
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        let cli = parse(&[]).unwrap();
+        assert!(matches!(cli.command, Command::Run));
+        assert_eq!(
+            cli.settings.config,
+            PathBuf::from(DEFAULT_SETTINGS_FILE_PATH)
+        );
+        assert!(matches!(
+            parse(&["validate"]).unwrap().command,
+            Command::Validate
+        ));
+        let cli = parse(&["screenshot", "shot.bmp"]).unwrap();
+        assert!(
+            matches!(cli.command, Command::Screenshot { path } if path == Path::new("shot.bmp"))
+        );
+        let cli = parse(&["bench", "--frames", "10"]).unwrap();
+        assert!(matches!(cli.command, Command::Bench { frames: 10 }));
+        let cli = parse(&["bench"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Bench {
+                frames: DEFAULT_BENCH_FRAMES
+            }
+        ));
+        assert!(matches!(
+            parse(&["bench", "--help"]).unwrap().command,
+            Command::Help
+        ));
+    }
+
+    #[test]
+    fn options_become_overrides() {
+        let cli = parse(&[
+            "--config",
+            "other.toml",
+            "--level",
+            "assets/map2.pbm",
+            "--windowed",
+            "--fps",
+            "30",
+            "--set",
+            "scene.fov = 90deg",
+        ])
+        .unwrap();
+        assert_eq!(cli.settings.config, PathBuf::from("other.toml"));
+        assert_eq!(
+            overrides(&cli),
+            [
+                ("level.map", "\"assets/map2.pbm\""),
+                ("scene.fullscreen", "false"),
+                ("scene.fps", "30"),
+                ("scene.fov", "90deg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn spawn_is_parsed() {
+        let cli = parse(&["--spawn", "2.5, 3"]).unwrap();
+        assert_eq!(
+            overrides(&cli),
+            [("level.player_x", "2.5"), ("level.player_y", "3.0")]
+        );
+        let cli = parse(&["--spawn", "1,2,90deg"]).unwrap();
+        let (key, angle) = overrides(&cli)[2];
+        assert_eq!(key, "level.player_angle");
+        assert!((angle.parse::<f32>().unwrap() - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert!(parse(&["--spawn", "1"]).is_err());
+        assert!(parse(&["--spawn", "a,2"]).is_err());
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(parse(&["--fps"])
+            .err()
+            .unwrap()
+            .contains("missing value for '--fps'"));
+        assert!(parse(&["--frames", "many"]).is_err());
+        assert!(parse(&["--set", "scene.fps"]).is_err());
+        assert!(parse(&["run", "validate"]).is_err());
+        assert!(parse(&["--unknown"])
+            .err()
+            .unwrap()
+            .contains("unexpected argument"));
+    }
+}
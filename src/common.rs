@@ -3,6 +3,7 @@ use crate::vectors::Vec2d;
 pub type Float = f32;
 pub type Float2d = Vec2d<Float>;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Size2d<T> {
     pub width: T,
     pub height: T,
@@ -26,17 +27,77 @@ pub enum DrawCommand {
         x2: i32,
         y2: i32,
     },
+    /// Limits drawing area for the following commands
+    ClipRect {
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+    },
+    ResetClip,
+    /// Character from the font atlas drawn with the current color
+    Glyph {
+        code: u8,
+        x: i32,
+        y: i32,
+        size: u32,
+    },
     SkyTexture {
         id: i32,
         offset: Float,
     },
+    /// Part of a texture between `src_x` and `src_x + src_width` relative to its width,
+    /// filled with the current color if the texture is missing
+    Sprite {
+        texture_id: i32,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        src_x: Float,
+        src_width: Float,
+        /// multiplies the texture colors, white keeps them
+        shade: (u8, u8, u8),
+    },
+    /// Wall column, `src_y` and `src_height` select the texture rows relative to its height
     Texture {
         depth: Float,
         x: i32,
         y: i32,
         offset: Float,
+        src_y: Float,
+        src_height: Float,
         width: u32,
         projected_height: u32,
         texture_id: i32,
+        /// multiplies the texture colors, white keeps them
+        shade: (u8, u8, u8),
     },
 }
+
+/// Draws a frame of `thickness` pixels along the screen edges
+pub fn draw_border(
+    commands: &mut Vec<DrawCommand>,
+    screen_size: ScreenSize,
+    color: (u8, u8, u8),
+    thickness: u32,
+) {
+    let ScreenSize { width, height } = screen_size;
+    commands.push(DrawCommand::ColorRGB(color.0, color.1, color.2));
+    [
+        (0, 0, width, thickness),
+        (0, height.saturating_sub(thickness) as i32, width, thickness),
+        (0, 0, thickness, height),
+        (width.saturating_sub(thickness) as i32, 0, thickness, height),
+    ]
+    .into_iter()
+    .for_each(|(x, y, w, h)| {
+        commands.push(DrawCommand::Rectangle {
+            x,
+            y,
+            w,
+            h,
+            fill: true,
+        })
+    });
+}
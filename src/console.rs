@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+
+use crate::{
+    common::{DrawCommand, ScreenSize},
+    control::ControllerState,
+    hud::{draw_text, GLYPH_SIZE},
+};
+
+const MAX_OUTPUT_LINES: usize = 100;
+const MAX_HISTORY: usize = 50;
+const PROMPT: &str = "> ";
+const SCALE: u32 = 2;
+
+/// What the scene should do after a key press in the console
+pub enum ConsoleAction {
+    Submit(String),
+    Complete,
+}
+
+/// Drop-down developer console
+#[derive(Default)]
+pub struct Console {
+    is_open: bool,
+    input: String,
+    history: Vec<String>,
+    history_index: Option<usize>,
+    output: VecDeque<String>,
+}
+
+impl Console {
+    pub const KEYCODE_TOGGLE: i32 = 96; // `
+    const KEYCODE_RETURN: i32 = 13;
+    const KEYCODE_BACKSPACE: i32 = 8;
+    const KEYCODE_TAB: i32 = 9;
+
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    pub fn toggle(&mut self) {
+        self.is_open = !self.is_open;
+    }
+
+    pub fn print<S: Into<String>>(&mut self, line: S) {
+        if self.output.len() == MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+        self.output.push_back(line.into());
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    pub fn on_text(&mut self, text: &str) {
+        self.input
+            .extend(text.chars().filter(|ch| *ch != '`' && !ch.is_control()));
+    }
+
+    pub fn on_key(&mut self, key_code: i32) -> Option<ConsoleAction> {
+        match key_code {
+            Self::KEYCODE_RETURN => {
+                let line = std::mem::take(&mut self.input);
+                let line = line.trim().to_string();
+                if line.is_empty() {
+                    return None;
+                }
+                self.print(format!("{PROMPT}{line}"));
+                if self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                    if self.history.len() > MAX_HISTORY {
+                        self.history.remove(0);
+                    }
+                }
+                self.history_index = None;
+                return Some(ConsoleAction::Submit(line));
+            }
+            Self::KEYCODE_BACKSPACE => {
+                self.input.pop();
+            }
+            Self::KEYCODE_TAB => return Some(ConsoleAction::Complete),
+            ControllerState::KEYCODE_UP => self.browse_history(-1),
+            ControllerState::KEYCODE_DOWN => self.browse_history(1),
+            _ => {}
+        }
+        None
+    }
+
+    fn browse_history(&mut self, direction: isize) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() as isize - 1;
+        let index = match self.history_index {
+            Some(index) => index as isize + direction,
+            None if direction < 0 => last,
+            None => return,
+        };
+        if index > last {
+            self.history_index = None;
+            self.input.clear();
+            return;
+        }
+        let index = index.max(0) as usize;
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// Completes the last word of the input using the candidates,
+    /// prints all of them if there are several matches
+    pub fn complete(&mut self, candidates: &[String]) {
+        let (head, word) = match self.input.rsplit_once(' ') {
+            Some((head, word)) => (format!("{head} "), word.to_string()),
+            None => (String::new(), self.input.clone()),
+        };
+        let matches = candidates
+            .iter()
+            .filter(|candidate| candidate.starts_with(&word))
+            .collect::<Vec<_>>();
+        let Some(first) = matches.first() else {
+            return;
+        };
+        // longest common prefix
+        let mut prefix = first.to_string();
+        for candidate in &matches[1..] {
+            while !candidate.starts_with(&prefix) {
+                prefix.pop();
+            }
+        }
+        if matches.len() == 1 {
+            prefix.push(' ');
+        } else {
+            let list = matches
+                .iter()
+                .map(|x| x.as_str())
+                .collect::<Vec<_>>()
+                .join("  ");
+            self.print(list);
+        }
+        self.input = head + &prefix;
+    }
+
+    pub fn draw(&self, commands: &mut Vec<DrawCommand>, screen_size: ScreenSize) {
+        if !self.is_open {
+            return;
+        }
+        let height = (screen_size.height * 2 / 5).max(GLYPH_SIZE * SCALE * 2);
+        commands.push(DrawCommand::ColorRGB(15, 15, 30));
+        commands.push(DrawCommand::Rectangle {
+            x: 0,
+            y: 0,
+            w: screen_size.width,
+            h: height,
+            fill: true,
+        });
+        commands.push(DrawCommand::ColorRGB(120, 120, 200));
+        commands.push(DrawCommand::Line {
+            x1: 0,
+            y1: height as i32,
+            x2: screen_size.width as i32,
+            y2: height as i32,
+        });
+        let line_height = (GLYPH_SIZE * SCALE) as i32 + 2;
+        let padding = 6;
+        let mut y = height as i32 - padding - line_height;
+        commands.push(DrawCommand::ColorRGB(255, 255, 255));
+        let input = format!("{PROMPT}{}_", self.input);
+        draw_text(commands, &input, padding, y, SCALE);
+        commands.push(DrawCommand::ColorRGB(180, 180, 180));
+        for line in self.output.iter().rev() {
+            y -= line_height;
+            if y < 0 {
+                break;
+            }
+            draw_text(commands, line, padding, y, SCALE);
+        }
+    }
+}
@@ -1,14 +1,8 @@
 pub enum ControlEvent {
-    Keyboard {
-        key_code: i32,
-        is_pressed: bool,
-    },
-    MouseMotion {
-        x: i32,
-        y: i32,
-        x_rel: i32,
-        y_rel: i32,
-    },
+    Keyboard { key_code: i32, is_pressed: bool },
+    MouseMotion { x_rel: i32 },
+    TextInput { text: String },
+    WindowResized { width: u32, height: u32 },
 }
 
 #[derive(Default)]
@@ -21,6 +15,20 @@ pub struct ControllerState {
     pub rotate_right_pressed: bool,
     pub mouse_x_relative: i32,
     pub minimap_visible: bool,
+    pub automap_visible: bool,
+    /// movement keys pan the automap instead of moving the player
+    pub automap_pan_mode: bool,
+    pub zoom_in_pressed: bool,
+    pub zoom_out_pressed: bool,
+    pub fullscreen: bool,
+    pub reload_requested: bool,
+    pub fire_pressed: bool,
+    /// weapon selected by number keys, zero based
+    pub weapon_slot: Option<usize>,
+    /// opens doors in front of the player
+    pub use_requested: bool,
+    pub quicksave_requested: bool,
+    pub quickload_requested: bool,
 }
 
 impl ControllerState {
@@ -28,11 +36,26 @@ impl ControllerState {
     const KEYCODE_S: i32 = 115;
     const KEYCODE_A: i32 = 97;
     const KEYCODE_D: i32 = 100;
+    const KEYCODE_E: i32 = 101;
+    const KEYCODE_F: i32 = 102;
+    const KEYCODE_TAB: i32 = 9;
+    const KEYCODE_SPACE: i32 = 32;
+    const KEYCODE_LCTRL: i32 = 1073742048;
+    const KEYCODE_1: i32 = 49;
+    const KEYCODE_9: i32 = 57;
     const KEYCODE_LEFT: i32 = 1073741904;
     const KEYCODE_RIGHT: i32 = 1073741903;
-    const KEYCODE_UP: i32 = 1073741906;
-    const KEYCODE_DOWN: i32 = 1073741905;
+    pub const KEYCODE_UP: i32 = 1073741906;
+    pub const KEYCODE_DOWN: i32 = 1073741905;
+    const KEYCODE_EQUALS: i32 = 61;
+    const KEYCODE_MINUS: i32 = 45;
+    const KEYCODE_KP_PLUS: i32 = 1073741911;
+    const KEYCODE_KP_MINUS: i32 = 1073741910;
     const KEYCODE_F2: i32 = 1073741883;
+    const KEYCODE_F5: i32 = 1073741886;
+    const KEYCODE_F6: i32 = 1073741887;
+    const KEYCODE_F9: i32 = 1073741890;
+    const KEYCODE_F11: i32 = 1073741892;
 
     pub fn on_key_event(&mut self, key_code: i32, is_pressed: bool) {
         match key_code {
@@ -42,7 +65,22 @@ impl ControllerState {
             Self::KEYCODE_D => self.right_pressed = is_pressed,
             Self::KEYCODE_LEFT => self.rotate_left_pressed = is_pressed,
             Self::KEYCODE_RIGHT => self.rotate_right_pressed = is_pressed,
+            Self::KEYCODE_EQUALS | Self::KEYCODE_KP_PLUS => self.zoom_in_pressed = is_pressed,
+            Self::KEYCODE_MINUS | Self::KEYCODE_KP_MINUS => self.zoom_out_pressed = is_pressed,
+            Self::KEYCODE_SPACE | Self::KEYCODE_LCTRL => self.fire_pressed = is_pressed,
+            Self::KEYCODE_1..=Self::KEYCODE_9 if is_pressed => {
+                self.weapon_slot = Some((key_code - Self::KEYCODE_1) as usize)
+            }
+            Self::KEYCODE_E if is_pressed => self.use_requested = true,
+            Self::KEYCODE_TAB if is_pressed => self.automap_visible = !self.automap_visible,
+            Self::KEYCODE_F if is_pressed && self.automap_visible => {
+                self.automap_pan_mode = !self.automap_pan_mode
+            }
             Self::KEYCODE_F2 if is_pressed => self.minimap_visible = !self.minimap_visible,
+            Self::KEYCODE_F5 if is_pressed => self.reload_requested = true,
+            Self::KEYCODE_F6 if is_pressed => self.quicksave_requested = true,
+            Self::KEYCODE_F9 if is_pressed => self.quickload_requested = true,
+            Self::KEYCODE_F11 if is_pressed => self.fullscreen = !self.fullscreen,
             _ => {
                 // don't care
                 // println!("Code {key_code}")
@@ -50,7 +88,23 @@ impl ControllerState {
         }
     }
 
+    /// Releases all held keys, toggles stay as is
+    pub fn release_keys(&mut self) {
+        *self = Self {
+            minimap_visible: self.minimap_visible,
+            automap_visible: self.automap_visible,
+            automap_pan_mode: self.automap_pan_mode,
+            fullscreen: self.fullscreen,
+            ..Self::default()
+        };
+    }
+
     pub fn reset_relative_values(&mut self) {
         self.mouse_x_relative = 0;
+        self.reload_requested = false;
+        self.weapon_slot = None;
+        self.use_requested = false;
+        self.quicksave_requested = false;
+        self.quickload_requested = false;
     }
 }
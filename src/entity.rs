@@ -0,0 +1,735 @@
+use std::{cmp::Reverse, collections::BinaryHeap, fmt};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    ai::{AiContext, EnemyAi},
+    common::{Float, Float2d},
+    inventory::ItemKind,
+    pathfinding::Pathfinder,
+    ray::cast,
+    settings::EntitySpawn,
+    walls::{overlaps_tile, Walls},
+};
+
+pub const TEXTURE_ID_ENEMY: i32 = 100;
+pub const TEXTURE_ID_ITEM: i32 = 101;
+pub const TEXTURE_ID_DECORATION: i32 = 102;
+pub const TEXTURE_ID_PROJECTILE: i32 = 103;
+pub const TEXTURE_ID_SPARK: i32 = 104;
+
+// seconds
+const PROJECTILE_LIFETIME: Float = 5.0;
+const SPARK_LIFETIME: Float = 0.15;
+/// sparks are pulled back from the hit point to not be hidden by the wall
+pub const HIT_OFFSET: Float = 0.05;
+
+/// Slot of an entity in the component storage, despawned slots are reused by new
+/// entities with the next generation so stale ids don't match them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EntityId {
+    index: usize,
+    generation: u32,
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Enemy,
+    Item,
+    Decoration,
+    Trigger,
+    /// spawned by weapons only
+    #[serde(skip)]
+    Projectile,
+    /// short-living visual effects like sparks, spawned at runtime only
+    #[serde(skip)]
+    Effect,
+}
+
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub position: Float2d,
+    pub angle: Float,
+}
+
+/// Billboard always facing the camera
+#[derive(Clone, Copy)]
+pub struct Sprite {
+    pub texture_id: i32,
+    /// fill color if the texture is missing
+    pub color: (u8, u8, u8),
+    /// relative to the wall height
+    pub height: Float,
+    /// bottom edge above the floor relative to the wall height
+    pub elevation: Float,
+}
+
+#[derive(Clone, Copy)]
+pub struct Collider {
+    pub radius: Float,
+    /// solid colliders block movement, others just detect overlapping
+    pub is_solid: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Projectile {
+    /// tiles per second
+    pub velocity: Float2d,
+    pub damage: i32,
+}
+
+/// Collected when the player touches the collider
+#[derive(Clone, Copy)]
+pub struct Pickup {
+    pub item: ItemKind,
+    pub amount: i32,
+}
+
+pub enum Behaviour {
+    /// Shows the message when the player touches the collider for the first time
+    Message { text: String, is_shown: bool },
+}
+
+/// Something an entity wants the scene to do
+pub enum EntityEvent {
+    Message(String),
+    PlayerHit {
+        damage: i32,
+    },
+    EnemyKilled {
+        name: Option<String>,
+    },
+    /// The player touches an item, the scene despawns it if the item is taken
+    Pickup {
+        id: EntityId,
+        pickup: Pickup,
+    },
+}
+
+/// Saved state of a level entity, runtime-only entities like projectiles aren't saved
+#[derive(Deserialize, Serialize)]
+pub struct EntitySnapshot {
+    pub health: Option<i32>,
+    pub is_message_shown: bool,
+    pub spawn: EntitySpawn,
+}
+
+/// Sparse storage of a single component type indexed by entity slot,
+/// values keep the generation of the entity they belong to
+pub struct Components<T> {
+    items: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Self { items: Vec::new() }
+    }
+}
+
+impl<T> Components<T> {
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        match self.items.get(id.index)? {
+            Some((generation, item)) if *generation == id.generation => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        match self.items.get_mut(id.index)? {
+            Some((generation, item)) if *generation == id.generation => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&mut self, id: EntityId, value: T) {
+        if id.index >= self.items.len() {
+            self.items.resize_with(id.index + 1, || None);
+        }
+        self.items[id.index] = Some((id.generation, value));
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        let item = self.items.get_mut(id.index)?;
+        let value = match item {
+            Some((generation, _)) if *generation == id.generation => item.take(),
+            _ => return None,
+        };
+        // drop empty slots at the end so short-living entities don't keep the storage grown
+        while let Some(None) = self.items.last() {
+            self.items.pop();
+        }
+        value.map(|(_, item)| item)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.items.iter().enumerate().filter_map(|(index, item)| {
+            let (generation, item) = item.as_ref()?;
+            let generation = *generation;
+            Some((EntityId { index, generation }, item))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.items
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let (generation, item) = item.as_mut()?;
+                let generation = *generation;
+                Some((EntityId { index, generation }, item))
+            })
+    }
+}
+
+/// Entity/component store, an entity is an id with any set of components
+#[derive(Default)]
+pub struct Entities {
+    kinds: Components<EntityKind>,
+    pub transforms: Components<Transform>,
+    pub sprites: Components<Sprite>,
+    pub colliders: Components<Collider>,
+    pub healths: Components<Health>,
+    pub behaviours: Components<Behaviour>,
+    pub ais: Components<EnemyAi>,
+    pub projectiles: Components<Projectile>,
+    pub pickups: Components<Pickup>,
+    pub names: Components<String>,
+    /// seconds left before the entity gets removed
+    pub lifetimes: Components<Float>,
+    pathfinder: Pathfinder,
+    /// current generation of every slot
+    generations: Vec<u32>,
+    /// slots of despawned entities to reuse, lowest first to keep the storage compact
+    free: BinaryHeap<Reverse<usize>>,
+}
+
+impl Entities {
+    /// Creates entities described by the level
+    pub fn with_spawns(spawns: &[EntitySpawn]) -> Self {
+        let mut entities = Self::default();
+        spawns.iter().for_each(|spawn| {
+            entities.spawn_from(spawn);
+        });
+        entities
+    }
+
+    /// Recreates entities saved by `snapshot`, enemies start idle
+    pub fn with_snapshots(snapshots: &[EntitySnapshot]) -> Self {
+        let mut entities = Self::default();
+        for snapshot in snapshots {
+            let id = entities.spawn_from(&snapshot.spawn);
+            if let (Some(health), Some(current)) = (entities.healths.get_mut(id), snapshot.health) {
+                health.current = current;
+            }
+            if let Some(Behaviour::Message { is_shown, .. }) = entities.behaviours.get_mut(id) {
+                *is_shown = snapshot.is_message_shown;
+            }
+        }
+        entities
+    }
+
+    /// Returns state of the level entities
+    pub fn snapshot(&self) -> Vec<EntitySnapshot> {
+        self.kinds
+            .iter()
+            .filter(|(_, kind)| !matches!(kind, EntityKind::Projectile | EntityKind::Effect))
+            .filter_map(|(id, kind)| {
+                let transform = self.transforms.get(id)?;
+                let pickup = self.pickups.get(id);
+                let (message, is_message_shown) = match self.behaviours.get(id) {
+                    Some(Behaviour::Message { text, is_shown }) => (Some(text.clone()), *is_shown),
+                    None => (None, false),
+                };
+                Some(EntitySnapshot {
+                    health: self.healths.get(id).map(|health| health.current),
+                    is_message_shown,
+                    spawn: EntitySpawn {
+                        kind: *kind,
+                        x: transform.position.x,
+                        y: transform.position.y,
+                        angle: transform.angle,
+                        name: self.names.get(id).cloned(),
+                        message,
+                        item: pickup.map(|pickup| pickup.item),
+                        amount: pickup.map(|pickup| pickup.amount),
+                    },
+                })
+            })
+            .collect()
+    }
+
+    pub fn spawn(&mut self, kind: EntityKind, transform: Transform) -> EntityId {
+        let id = match self.free.pop() {
+            Some(Reverse(index)) => EntityId {
+                index,
+                generation: self.generations[index],
+            },
+            None => {
+                self.generations.push(0);
+                EntityId {
+                    index: self.generations.len() - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.kinds.insert(id, kind);
+        self.transforms.insert(id, transform);
+        id
+    }
+
+    /// Spawns entity with default components for its kind
+    pub fn spawn_from(&mut self, spawn: &EntitySpawn) -> EntityId {
+        let transform = Transform {
+            position: Float2d::new(spawn.x, spawn.y),
+            angle: spawn.angle,
+        };
+        let id = self.spawn(spawn.kind, transform);
+        match spawn.kind {
+            EntityKind::Enemy => {
+                self.sprites.insert(
+                    id,
+                    Sprite {
+                        texture_id: TEXTURE_ID_ENEMY,
+                        color: (200, 40, 40),
+                        height: 0.8,
+                        elevation: 0.0,
+                    },
+                );
+                self.colliders.insert(
+                    id,
+                    Collider {
+                        radius: 0.3,
+                        is_solid: true,
+                    },
+                );
+                self.healths.insert(
+                    id,
+                    Health {
+                        current: 100,
+                        max: 100,
+                    },
+                );
+                self.ais.insert(id, EnemyAi::default());
+            }
+            EntityKind::Item => {
+                let item = spawn.item.unwrap_or_default();
+                self.sprites.insert(
+                    id,
+                    Sprite {
+                        texture_id: TEXTURE_ID_ITEM,
+                        color: item.color(),
+                        height: 0.3,
+                        elevation: 0.0,
+                    },
+                );
+                self.colliders.insert(
+                    id,
+                    Collider {
+                        radius: 0.3,
+                        is_solid: false,
+                    },
+                );
+                self.pickups.insert(
+                    id,
+                    Pickup {
+                        item,
+                        amount: spawn.amount.unwrap_or_else(|| item.default_amount()),
+                    },
+                );
+            }
+            EntityKind::Decoration => {
+                self.sprites.insert(
+                    id,
+                    Sprite {
+                        texture_id: TEXTURE_ID_DECORATION,
+                        color: (120, 140, 120),
+                        height: 1.0,
+                        elevation: 0.0,
+                    },
+                );
+                self.colliders.insert(
+                    id,
+                    Collider {
+                        radius: 0.3,
+                        is_solid: true,
+                    },
+                );
+            }
+            EntityKind::Trigger => {
+                self.colliders.insert(
+                    id,
+                    Collider {
+                        radius: 0.5,
+                        is_solid: false,
+                    },
+                );
+            }
+            EntityKind::Projectile | EntityKind::Effect => {}
+        }
+        if let Some(name) = &spawn.name {
+            self.names.insert(id, name.clone());
+        }
+        if let Some(text) = &spawn.message {
+            self.behaviours.insert(
+                id,
+                Behaviour::Message {
+                    text: text.clone(),
+                    is_shown: false,
+                },
+            );
+        }
+        id
+    }
+
+    pub fn spawn_projectile(&mut self, position: Float2d, velocity: Float2d, damage: i32) {
+        let transform = Transform {
+            position,
+            angle: velocity.y.atan2(velocity.x),
+        };
+        let id = self.spawn(EntityKind::Projectile, transform);
+        self.sprites.insert(
+            id,
+            Sprite {
+                texture_id: TEXTURE_ID_PROJECTILE,
+                color: (255, 140, 40),
+                height: 0.15,
+                elevation: 0.4,
+            },
+        );
+        self.projectiles.insert(id, Projectile { velocity, damage });
+        self.lifetimes.insert(id, PROJECTILE_LIFETIME);
+    }
+
+    /// Spawns a short flash at the point where a shot hits something
+    pub fn spawn_spark(&mut self, position: Float2d) {
+        let transform = Transform {
+            position,
+            angle: 0.0,
+        };
+        let id = self.spawn(EntityKind::Effect, transform);
+        self.sprites.insert(
+            id,
+            Sprite {
+                texture_id: TEXTURE_ID_SPARK,
+                color: (255, 240, 150),
+                height: 0.08,
+                elevation: 0.46,
+            },
+        );
+        self.lifetimes.insert(id, SPARK_LIFETIME);
+    }
+
+    pub fn is_alive(&self, id: EntityId) -> bool {
+        self.generations.get(id.index) == Some(&id.generation)
+    }
+
+    pub fn despawn(&mut self, id: EntityId) {
+        if !self.is_alive(id) {
+            return;
+        }
+        self.kinds.remove(id);
+        self.transforms.remove(id);
+        self.sprites.remove(id);
+        self.colliders.remove(id);
+        self.healths.remove(id);
+        self.behaviours.remove(id);
+        self.ais.remove(id);
+        self.projectiles.remove(id);
+        self.pickups.remove(id);
+        self.names.remove(id);
+        self.lifetimes.remove(id);
+        self.generations[id.index] = id.generation.wrapping_add(1);
+        self.free.push(Reverse(id.index));
+    }
+
+    /// Finds the closest entity with health hit by the ray within `max_depth`,
+    /// returns it with the hit distance
+    pub fn raycast(
+        &self,
+        origin: Float2d,
+        angle: Float,
+        max_depth: Float,
+    ) -> Option<(EntityId, Float)> {
+        let (cos_a, sin_a) = (angle.cos(), angle.sin());
+        self.healths
+            .iter()
+            .filter_map(|(id, _)| {
+                let radius = self.colliders.get(id)?.radius;
+                let Float2d { x, y } = self.position(id)? - origin;
+                // ray-circle intersection
+                let along = x * cos_a + y * sin_a;
+                let across = x * sin_a - y * cos_a;
+                if along < 0.0 || across.abs() > radius {
+                    return None;
+                }
+                let depth = along - (radius * radius - across * across).sqrt();
+                (depth <= max_depth).then_some((id, depth.max(0.0)))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Reduces entity's health, killed entities are removed.
+    /// `source` is where the attack came from, enemies start chasing it
+    pub fn damage(&mut self, id: EntityId, amount: i32, source: Float2d) -> Option<EntityEvent> {
+        let health = self.healths.get_mut(id)?;
+        health.current -= amount;
+        if health.current <= 0 {
+            let name = self.names.get(id).cloned();
+            self.despawn(id);
+            return Some(EntityEvent::EnemyKilled { name });
+        }
+        if let Some(ai) = self.ais.get_mut(id) {
+            ai.alert(source);
+        }
+        None
+    }
+
+    fn update_projectiles(&mut self, delta_time: Float, walls: &Walls) -> Vec<EntityEvent> {
+        let mut events = Vec::new();
+        let projectiles = self
+            .projectiles
+            .iter()
+            .map(|(id, projectile)| (id, *projectile))
+            .collect::<Vec<_>>();
+        for (id, projectile) in projectiles {
+            let Some(transform) = self.transforms.get_mut(id) else {
+                continue;
+            };
+            let start = transform.position;
+            let Float2d { x, y } = projectile.velocity;
+            let distance = x.hypot(y) * delta_time;
+            let angle = y.atan2(x);
+            let wall_depth = cast(walls, start, angle, distance);
+            let target = self.raycast(start, angle, wall_depth.unwrap_or(distance));
+            let hit_depth = match (target, wall_depth) {
+                (Some((target, depth)), _) => {
+                    events.extend(self.damage(target, projectile.damage, start));
+                    depth
+                }
+                (None, Some(depth)) => depth,
+                (None, None) => {
+                    let offset = Float2d::new(x * delta_time, y * delta_time);
+                    if let Some(transform) = self.transforms.get_mut(id) {
+                        transform.position += offset;
+                    }
+                    continue;
+                }
+            };
+            self.despawn(id);
+            let depth = (hit_depth - HIT_OFFSET).max(0.0);
+            self.spawn_spark(start + Float2d::new(depth * angle.cos(), depth * angle.sin()));
+        }
+        events
+    }
+
+    fn update_lifetimes(&mut self, delta_time: Float) {
+        let mut expired = Vec::new();
+        for (id, time_left) in self.lifetimes.iter_mut() {
+            *time_left -= delta_time;
+            if *time_left <= 0.0 {
+                expired.push(id);
+            }
+        }
+        expired.into_iter().for_each(|id| self.despawn(id));
+    }
+
+    pub fn enemies_left(&self) -> usize {
+        self.ais.iter().count()
+    }
+
+    pub fn items_left(&self) -> usize {
+        self.pickups.iter().count()
+    }
+
+    pub fn position(&self, id: EntityId) -> Option<Float2d> {
+        self.transforms.get(id).map(|transform| transform.position)
+    }
+
+    /// Checks if any collider overlaps the tile
+    pub fn occupies_tile(&self, col: i32, row: i32) -> bool {
+        self.colliders.iter().any(|(id, collider)| {
+            self.position(id)
+                .is_some_and(|position| overlaps_tile(position, collider.radius, col, row))
+        })
+    }
+
+    /// Checks if a circle at `point` overlaps any solid collider
+    pub fn is_blocked(&self, point: Float2d, radius: Float) -> bool {
+        self.colliders
+            .iter()
+            .any(|(id, collider)| collider.is_solid && self.overlaps(id, collider, point, radius))
+    }
+
+    fn overlaps(&self, id: EntityId, collider: &Collider, point: Float2d, radius: Float) -> bool {
+        let Some(position) = self.position(id) else {
+            return false;
+        };
+        let Float2d { x, y } = position - point;
+        x.hypot(y) < collider.radius + radius
+    }
+
+    /// Runs behaviours, returns events for the scene
+    pub fn update(
+        &mut self,
+        delta_time: Float,
+        walls: &Walls,
+        player_pos: Float2d,
+        player_radius: Float,
+    ) -> Vec<EntityEvent> {
+        let mut events = Vec::new();
+        self.pathfinder.begin_frame();
+        let mut context = AiContext {
+            walls,
+            pathfinder: &mut self.pathfinder,
+            player_pos,
+            delta_time,
+        };
+        let ids = self.ais.iter().map(|(id, _)| id).collect::<Vec<_>>();
+        for id in ids {
+            let (Some(ai), Some(transform)) = (self.ais.get_mut(id), self.transforms.get_mut(id))
+            else {
+                continue;
+            };
+            events.extend(ai.update(transform, &mut context));
+        }
+        events.extend(self.update_projectiles(delta_time, walls));
+        self.update_lifetimes(delta_time);
+        let touched = self
+            .colliders
+            .iter()
+            .filter(|(id, collider)| self.overlaps(*id, collider, player_pos, player_radius))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in touched {
+            if let Some(pickup) = self.pickups.get(id) {
+                events.push(EntityEvent::Pickup {
+                    id,
+                    pickup: *pickup,
+                });
+            }
+            let Some(behaviour) = self.behaviours.get_mut(id) else {
+                continue;
+            };
+            match behaviour {
+                Behaviour::Message { text, is_shown } if !*is_shown => {
+                    *is_shown = true;
+                    events.push(EntityEvent::Message(text.clone()));
+                }
+                Behaviour::Message { .. } => {}
+            }
+        }
+        events
+    }
+
+    /// Returns items left on the level with their positions
+    pub fn items(&self) -> impl Iterator<Item = (Float2d, ItemKind)> + '_ {
+        self.pickups
+            .iter()
+            .filter_map(|(id, pickup)| self.position(id).map(|position| (position, pickup.item)))
+    }
+
+    /// Returns visible sprites with their positions
+    pub fn sprites(&self) -> impl Iterator<Item = (Float2d, Sprite)> + '_ {
+        self.sprites
+            .iter()
+            .filter_map(|(id, sprite)| self.position(id).map(|position| (position, *sprite)))
+    }
+
+    /// One line description per entity for debugging
+    pub fn describe(&self) -> Vec<String> {
+        self.kinds
+            .iter()
+            .map(|(id, kind)| {
+                let mut line = format!("#{id} {kind:?}");
+                if let Some(name) = self.names.get(id) {
+                    line += &format!(" '{name}'");
+                }
+                if let Some(Transform { position, angle }) = self.transforms.get(id) {
+                    line += &format!(
+                        " at {:.2} {:.2} facing {:.0} deg",
+                        position.x,
+                        position.y,
+                        angle.to_degrees()
+                    );
+                }
+                if let Some(health) = self.healths.get(id) {
+                    line += &format!(" hp {}/{}", health.current, health.max);
+                }
+                if let Some(ai) = self.ais.get(id) {
+                    line += &format!(" {:?}", ai.state());
+                }
+                if let Some(Pickup { item, amount }) = self.pickups.get(id) {
+                    line += &format!(" {} x{amount}", item.name());
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn(entities: &mut Entities) -> EntityId {
+        let transform = Transform {
+            position: Float2d::new(1.5, 1.5),
+            angle: 0.0,
+        };
+        entities.spawn(EntityKind::Decoration, transform)
+    }
+
+    #[test]
+    fn despawned_slots_are_reused() {
+        let mut entities = Entities::default();
+        let first = spawn(&mut entities);
+        let second = spawn(&mut entities);
+        entities.despawn(first);
+        let third = spawn(&mut entities);
+        assert_eq!(third.index, first.index);
+        assert_ne!(third, first);
+        assert_eq!(entities.generations.len(), 2);
+        assert!(entities.is_alive(second));
+    }
+
+    #[test]
+    fn stale_ids_dont_match_new_entities() {
+        let mut entities = Entities::default();
+        let stale = spawn(&mut entities);
+        entities.despawn(stale);
+        let id = spawn(&mut entities);
+        assert!(entities.transforms.get(stale).is_none());
+        assert!(entities.transforms.get(id).is_some());
+        entities.despawn(stale);
+        assert!(entities.is_alive(id));
+        assert_eq!(entities.kinds.iter().count(), 1);
+    }
+
+    #[test]
+    fn expired_sparks_free_their_slots() {
+        let mut entities = Entities::default();
+        let decoration = spawn(&mut entities);
+        for i in 0..10 {
+            entities.spawn_spark(Float2d::new(i as Float, 1.0));
+        }
+        entities.update_lifetimes(SPARK_LIFETIME);
+        assert_eq!(entities.kinds.items.len(), decoration.index + 1);
+        assert_eq!(entities.lifetimes.items.len(), 0);
+        entities.spawn_spark(Float2d::new(1.0, 1.0));
+        assert_eq!(entities.generations.len(), 11);
+        assert_eq!(entities.kinds.items.len(), 2);
+    }
+}
@@ -0,0 +1,71 @@
+use std::{fs, path::Path};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::settings::LevelInfo;
+
+/// Levels played one after another, player's stats carry over between them
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Episode {
+    pub name: String,
+    /// `[[levels]]` tables with the same fields as the `[level]` settings section
+    pub levels: Vec<LevelInfo>,
+}
+
+impl Episode {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content =
+            fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let episode = toml::from_str::<Episode>(&content)
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        if episode.levels.is_empty() {
+            return Err(format!("{}: episode has no levels", path.display()));
+        }
+        for (i, level) in episode.levels.iter().enumerate() {
+            level.validate().map_err(|(key, reason)| {
+                format!(
+                    "{}: level #{} ({}): `{key}` {reason}",
+                    path.display(),
+                    i + 1,
+                    level.title()
+                )
+            })?;
+        }
+        Ok(episode)
+    }
+}
+
+/// Level progress shown on the intermission screen
+#[derive(Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LevelStats {
+    pub kills: u32,
+    pub total_kills: u32,
+    pub items: u32,
+    pub total_items: u32,
+    /// indices of the level secrets found by the player, push walls follow the secret areas
+    pub secrets: Vec<usize>,
+    pub total_secrets: u32,
+}
+
+impl LevelStats {
+    pub fn new(total_kills: usize, total_items: usize, total_secrets: usize) -> Self {
+        Self {
+            total_kills: total_kills as u32,
+            total_items: total_items as u32,
+            total_secrets: total_secrets as u32,
+            ..Self::default()
+        }
+    }
+
+    /// Lines of the intermission summary
+    pub fn summary(&self) -> Vec<String> {
+        vec![
+            format!("Kills {}/{}", self.kills, self.total_kills),
+            format!("Items {}/{}", self.items, self.total_items),
+            format!("Secrets {}/{}", self.secrets.len(), self.total_secrets),
+        ]
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+
+use crate::{
+    common::{draw_border, DrawCommand, Float, ScreenSize},
+    inventory::KeyColor,
+    settings::{HudSettings, Quality},
+};
+
+pub const TEXTURE_ID_FONT: i32 = 998;
+/// Glyph cell size in the font atlas, glyphs are placed by their ASCII codes starting from space
+pub const GLYPH_SIZE: u32 = 8;
+pub const GLYPH_COLUMNS: u32 = 16;
+
+const MAX_MESSAGES: usize = 4;
+const PADDING: i32 = 10;
+
+/// Pushes glyph commands for the text, non-ASCII characters are replaced with '?'
+pub fn draw_text(commands: &mut Vec<DrawCommand>, text: &str, x: i32, y: i32, scale: u32) {
+    let size = GLYPH_SIZE * scale;
+    for (i, ch) in text.chars().enumerate() {
+        if ch == ' ' {
+            continue;
+        }
+        let code = if ch.is_ascii_graphic() {
+            ch as u8
+        } else {
+            b'?'
+        };
+        commands.push(DrawCommand::Glyph {
+            code,
+            x: x + (i as u32 * size) as i32,
+            y,
+            size,
+        });
+    }
+}
+
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    text.chars().count() as u32 * GLYPH_SIZE * scale
+}
+
+/// Player's values shown on the HUD, missing values aren't drawn
+#[derive(Default)]
+pub struct HudStatus {
+    pub health: Option<i32>,
+    pub armor: Option<i32>,
+    pub ammo: Option<u32>,
+    pub weapon: Option<&'static str>,
+    pub score: Option<u32>,
+    /// collected keys, drawn as colored squares
+    pub keys: Vec<KeyColor>,
+    /// red screen border intensity in range 0..1
+    pub hurt_flash: Float,
+}
+
+struct Message {
+    text: String,
+    time_left: Float,
+}
+
+pub struct Hud {
+    opts: HudSettings,
+    screen_size: ScreenSize,
+    messages: VecDeque<Message>,
+    // fps counter
+    frames: usize,
+    frames_time: Float,
+    fps: usize,
+    frame_time: Float,
+}
+
+impl Hud {
+    pub fn new(opts: HudSettings, screen_size: ScreenSize) -> Self {
+        Self {
+            opts,
+            screen_size,
+            messages: VecDeque::new(),
+            frames: 0,
+            frames_time: 0.0,
+            fps: 0,
+            frame_time: 0.0,
+        }
+    }
+
+    pub fn set_settings(&mut self, opts: HudSettings) {
+        self.opts = opts;
+    }
+
+    pub fn resize(&mut self, screen_size: ScreenSize) {
+        self.screen_size = screen_size;
+    }
+
+    pub fn show_message<S: Into<String>>(&mut self, text: S) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            text: text.into(),
+            time_left: self.opts.message_time,
+        });
+    }
+
+    pub fn update(&mut self, delta_time: Float) {
+        self.frames += 1;
+        self.frames_time += delta_time;
+        if self.frames_time >= 1.0 {
+            self.fps = self.frames;
+            self.frames = 0;
+            self.frames_time = 0.0;
+        }
+        self.messages
+            .iter_mut()
+            .for_each(|message| message.time_left -= delta_time);
+        self.messages.retain(|message| message.time_left > 0.0);
+    }
+
+    /// Time spent on the last frame without vsync waiting, in seconds
+    pub fn set_frame_time(&mut self, frame_time: Float) {
+        self.frame_time = frame_time;
+    }
+
+    pub fn draw(&self, commands: &mut Vec<DrawCommand>, status: &HudStatus, quality: Quality) {
+        let scale = self.opts.scale;
+        let line_height = (GLYPH_SIZE * scale) as i32 + 4;
+        let ScreenSize { width, height } = self.screen_size;
+        if status.hurt_flash > 0.0 {
+            self.draw_hurt_flash(commands, status.hurt_flash);
+        }
+        if self.opts.crosshair {
+            self.draw_crosshair(commands);
+        }
+        let mut y = PADDING;
+        if self.opts.show_fps {
+            let text = format!(
+                "FPS {} {:.1}ms {quality}",
+                self.fps,
+                self.frame_time * 1000.0
+            );
+            self.draw_line(commands, &text, PADDING, y, (255, 255, 0));
+            y += line_height;
+        }
+        for message in &self.messages {
+            self.draw_line(commands, &message.text, PADDING, y, (255, 255, 255));
+            y += line_height;
+        }
+        let bottom = height as i32 - PADDING - line_height;
+        let mut x = PADDING;
+        if let Some(health) = status.health {
+            let text = format!("HEALTH {health}");
+            self.draw_line(commands, &text, x, bottom, (220, 60, 60));
+            x += text_width(&text, scale) as i32 + 2 * PADDING;
+        }
+        if let Some(armor) = status.armor {
+            let text = format!("ARMOR {armor}");
+            self.draw_line(commands, &text, x, bottom, (80, 140, 230));
+            x += text_width(&text, scale) as i32 + 2 * PADDING;
+        }
+        if let Some(score) = status.score {
+            let text = format!("SCORE {score}");
+            self.draw_line(commands, &text, x, bottom, (250, 220, 60));
+        }
+        let key_size = GLYPH_SIZE * scale;
+        for (i, key) in status.keys.iter().rev().enumerate() {
+            let (r, g, b) = key.rgb();
+            commands.push(DrawCommand::ColorRGB(r, g, b));
+            commands.push(DrawCommand::Rectangle {
+                x: width as i32 - PADDING - ((i as u32 + 1) * (key_size + 4)) as i32 + 4,
+                y: bottom - line_height,
+                w: key_size,
+                h: key_size,
+                fill: true,
+            });
+        }
+        if let Some(ammo) = status.ammo {
+            let text = match status.weapon {
+                Some(weapon) => format!("{} AMMO {ammo}", weapon.to_uppercase()),
+                None => format!("AMMO {ammo}"),
+            };
+            let x = width as i32 - PADDING - text_width(&text, scale) as i32;
+            self.draw_line(commands, &text, x, bottom, (230, 200, 80));
+        }
+    }
+
+    fn draw_line(
+        &self,
+        commands: &mut Vec<DrawCommand>,
+        text: &str,
+        x: i32,
+        y: i32,
+        (r, g, b): (u8, u8, u8),
+    ) {
+        let scale = self.opts.scale;
+        // shadow
+        commands.push(DrawCommand::ColorRGB(0, 0, 0));
+        draw_text(commands, text, x + scale as i32, y + scale as i32, scale);
+        commands.push(DrawCommand::ColorRGB(r, g, b));
+        draw_text(commands, text, x, y, scale);
+    }
+
+    fn draw_hurt_flash(&self, commands: &mut Vec<DrawCommand>, intensity: Float) {
+        let ScreenSize { width, height } = self.screen_size;
+        let thickness = ((width.min(height) as Float * 0.05 * intensity) as u32).max(1);
+        draw_border(commands, self.screen_size, (200, 0, 0), thickness);
+    }
+
+    fn draw_crosshair(&self, commands: &mut Vec<DrawCommand>) {
+        let size = 4 * self.opts.scale as i32;
+        let (x, y) = (
+            self.screen_size.width as i32 >> 1,
+            self.screen_size.height as i32 >> 1,
+        );
+        commands.push(DrawCommand::ColorRGB(255, 255, 255));
+        commands.push(DrawCommand::Line {
+            x1: x - size,
+            y1: y,
+            x2: x + size,
+            y2: y,
+        });
+        commands.push(DrawCommand::Line {
+            x1: x,
+            y1: y - size,
+            x2: x,
+            y2: y + size,
+        });
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt::Display;
+
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyColor {
+    Red,
+    Blue,
+    Yellow,
+}
+
+impl KeyColor {
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        match self {
+            Self::Red => (220, 40, 40),
+            Self::Blue => (50, 90, 230),
+            Self::Yellow => (230, 210, 40),
+        }
+    }
+}
+
+impl Display for KeyColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Red => "red",
+            Self::Blue => "blue",
+            Self::Yellow => "yellow",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemKind {
+    Health,
+    Armor,
+    /// pistol bullets
+    Ammo,
+    /// launcher rockets
+    Rockets,
+    RedKey,
+    BlueKey,
+    YellowKey,
+    /// score item
+    #[default]
+    Treasure,
+}
+
+impl ItemKind {
+    /// Amount given if the level doesn't specify it
+    pub fn default_amount(&self) -> i32 {
+        match self {
+            Self::Health => 25,
+            Self::Armor => 50,
+            Self::Ammo => 20,
+            Self::Rockets => 5,
+            Self::RedKey | Self::BlueKey | Self::YellowKey => 1,
+            Self::Treasure => 100,
+        }
+    }
+
+    pub fn key_color(&self) -> Option<KeyColor> {
+        match self {
+            Self::RedKey => Some(KeyColor::Red),
+            Self::BlueKey => Some(KeyColor::Blue),
+            Self::YellowKey => Some(KeyColor::Yellow),
+            _ => None,
+        }
+    }
+
+    /// Sprite color if the texture is missing, also used by the automap
+    pub fn color(&self) -> (u8, u8, u8) {
+        match self {
+            Self::Health => (240, 240, 240),
+            Self::Armor => (80, 140, 230),
+            Self::Ammo | Self::Rockets => (200, 160, 60),
+            Self::Treasure => (250, 220, 60),
+            Self::RedKey | Self::BlueKey | Self::YellowKey => self
+                .key_color()
+                .map(|color| color.rgb())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Health => "health",
+            Self::Armor => "armor",
+            Self::Ammo => "ammo",
+            Self::Rockets => "rockets",
+            Self::RedKey => "red key",
+            Self::BlueKey => "blue key",
+            Self::YellowKey => "yellow key",
+            Self::Treasure => "treasure",
+        }
+    }
+}
+
+/// Keys and score collected by the player
+#[derive(Default, Clone, Deserialize, Serialize)]
+pub struct Inventory {
+    keys: Vec<KeyColor>,
+    score: u32,
+}
+
+impl Inventory {
+    pub fn has_key(&self, color: KeyColor) -> bool {
+        self.keys.contains(&color)
+    }
+
+    /// Returns false if the key is already collected
+    pub fn add_key(&mut self, color: KeyColor) -> bool {
+        if self.has_key(color) {
+            return false;
+        }
+        self.keys.push(color);
+        true
+    }
+
+    pub fn keys(&self) -> &[KeyColor] {
+        &self.keys
+    }
+
+    pub fn add_score(&mut self, amount: u32) {
+        self.score = self.score.saturating_add(amount);
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+}
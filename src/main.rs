@@ -1,27 +1,113 @@
+mod ai;
+mod automap;
 mod background;
+mod cli;
 mod common;
+mod console;
 mod control;
+mod entity;
+mod episode;
+mod hud;
+mod inventory;
+mod menu;
+mod minimap;
+mod pathfinding;
 mod pbm;
 mod player;
+mod ray;
 mod raycaster;
+mod reload;
 mod renderer;
+mod resolution;
+mod save;
 mod scene;
+mod sectors;
 mod settings;
+mod triggers;
 mod vectors;
 mod walls;
+mod weapon;
 
+use std::env;
+
+use cli::{Cli, Command, USAGE};
+use episode::Episode;
 use renderer::RendererSDL;
 use scene::Scene;
-use settings::Settings;
-
-const SETTINGS_FILE_PATH: &str = "raycaster.toml";
+use settings::SettingsSource;
+use walls::validate_level;
 
 fn main() -> Result<(), String> {
-    let Ok(settings) = Settings::with_file(SETTINGS_FILE_PATH) else {
-        return Err("Failed to read settings".to_string());
-    };
-    let mut scene = Scene::new(settings);
-    let mut renderer = RendererSDL::new(&mut scene)?;
-    renderer.run()?;
+    let cli = Cli::parse(env::args().skip(1))?;
+    match cli.command {
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Command::Run => {
+            let mut scene = load_scene(cli.settings)?;
+            let mut renderer = RendererSDL::new(&mut scene)?;
+            renderer.run()
+        }
+        Command::Validate => validate(&cli.settings),
+        Command::Screenshot { path } => {
+            let mut scene = load_scene(cli.settings)?;
+            let mut renderer = RendererSDL::headless(&mut scene)?;
+            renderer.screenshot(&path)?;
+            println!("Screenshot saved to {}", path.display());
+            Ok(())
+        }
+        Command::Bench { frames } => {
+            let mut scene = load_scene(cli.settings)?;
+            let mut renderer = RendererSDL::headless(&mut scene)?;
+            let frame_times = renderer.bench(frames)?;
+            print_bench_report(frame_times);
+            Ok(())
+        }
+    }
+}
+
+fn load_scene(source: SettingsSource) -> Result<Scene, String> {
+    let settings = source.load().map_err(|err| err.to_string())?;
+    Ok(Scene::new(settings, source))
+}
+
+fn validate(source: &SettingsSource) -> Result<(), String> {
+    let settings = source.load().map_err(|err| err.to_string())?;
+    let file = source.path().display().to_string();
+    let mut errors = validate_level(&settings.level, &file)?;
+    if let Some(path) = &settings.campaign.episode {
+        let episode = Episode::load(path)?;
+        for (i, level_info) in episode.levels.iter().enumerate() {
+            let file = format!("{path} (level #{})", i + 1);
+            errors.extend(validate_level(level_info, &file)?);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    println!("{}: OK", source.path().display());
     Ok(())
 }
+
+fn print_bench_report(mut frame_times: Vec<f32>) {
+    if frame_times.is_empty() {
+        println!("No frames rendered");
+        return;
+    }
+    frame_times.sort_by(|a, b| a.total_cmp(b));
+    let count = frame_times.len();
+    let total = frame_times.iter().sum::<f32>();
+    let average = total / count as f32;
+    let percentile = |p: usize| frame_times[(count * p / 100).min(count - 1)];
+    println!("Frames: {count}, total: {total:.2} s");
+    println!(
+        "Frame time (ms) avg: {:.2}, min: {:.2}, p50: {:.2}, p95: {:.2}, max: {:.2}",
+        average * 1000.0,
+        frame_times[0] * 1000.0,
+        percentile(50) * 1000.0,
+        percentile(95) * 1000.0,
+        frame_times[count - 1] * 1000.0
+    );
+    println!("Average FPS: {:.1}", 1.0 / average);
+}
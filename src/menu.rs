@@ -0,0 +1,392 @@
+use std::{fs, path::Path};
+
+use crate::{
+    common::{DrawCommand, Float, ScreenSize},
+    control::ControllerState,
+    episode::{Episode, LevelStats},
+    hud::{draw_text, text_width, GLYPH_SIZE},
+    settings::{Quality, Settings},
+};
+
+const TITLE_SCALE: u32 = 4;
+const ITEM_SCALE: u32 = 2;
+const PADDING: i32 = 24;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Screen {
+    MainMenu,
+    LevelSelect,
+    Pause,
+    Options,
+    LevelComplete,
+    Death,
+}
+
+impl Screen {
+    fn title(&self) -> &'static str {
+        match self {
+            Self::MainMenu => "RAYCASTER",
+            Self::LevelSelect => "Select level",
+            Self::Pause => "Paused",
+            Self::Options => "Options",
+            Self::LevelComplete => "Level complete",
+            Self::Death => "You died",
+        }
+    }
+
+    /// Full-screen menus hide the game view
+    fn is_opaque(&self) -> bool {
+        matches!(self, Self::MainMenu | Self::LevelSelect)
+    }
+}
+
+/// What the scene should do when a menu item gets activated
+pub enum MenuAction {
+    Open(Screen),
+    Back,
+    Resume,
+    NewGame,
+    StartLevel(String),
+    /// Starts the episode's level by its index
+    StartEpisodeLevel(usize),
+    NextLevel,
+    Restart,
+    MainMenu,
+    /// Changes settings value, the same as the `set` console command
+    SetValue(&'static str, String),
+    ToggleMinimap,
+    ToggleFullscreen,
+    Quit,
+}
+
+struct MenuItem {
+    label: String,
+    action: MenuAction,
+}
+
+impl MenuItem {
+    fn new<S: Into<String>>(label: S, action: MenuAction) -> Self {
+        Self {
+            label: label.into(),
+            action,
+        }
+    }
+}
+
+/// Game state visible to the menu
+pub struct MenuContext<'a> {
+    pub settings: &'a Settings,
+    pub controller_state: &'a ControllerState,
+    /// a level was started and may be continued
+    pub has_session: bool,
+    pub level_time: Float,
+    pub stats: &'a LevelStats,
+    pub episode: Option<&'a Episode>,
+    /// index of the episode's level being played
+    pub episode_level: Option<usize>,
+}
+
+impl MenuContext<'_> {
+    /// Title of the episode's level following the current one
+    fn next_level(&self) -> Option<String> {
+        let episode = self.episode?;
+        let index = self.episode_level? + 1;
+        episode.levels.get(index).map(|level| level.title())
+    }
+}
+
+struct Page {
+    screen: Screen,
+    selected: usize,
+}
+
+/// Stack of menu screens, only the top one receives input
+#[derive(Default)]
+pub struct Menu {
+    stack: Vec<Page>,
+    levels: Vec<String>,
+}
+
+impl Menu {
+    pub const KEYCODE_ESCAPE: i32 = 27;
+    const KEYCODE_RETURN: i32 = 13;
+
+    pub fn is_active(&self) -> bool {
+        !self.stack.is_empty()
+    }
+
+    pub fn top(&self) -> Option<Screen> {
+        self.stack.last().map(|page| page.screen)
+    }
+
+    pub fn push(&mut self, screen: Screen) {
+        self.stack.push(Page {
+            screen,
+            selected: 0,
+        });
+    }
+
+    pub fn pop(&mut self) {
+        self.stack.pop();
+    }
+
+    /// Replaces the whole stack with a single screen
+    pub fn reset(&mut self, screen: Screen) {
+        self.stack.clear();
+        self.push(screen);
+    }
+
+    pub fn clear(&mut self) {
+        self.stack.clear();
+    }
+
+    /// Sets maps listed on the level select screen
+    pub fn set_levels(&mut self, levels: Vec<String>) {
+        self.levels = levels;
+    }
+
+    pub fn on_key(&mut self, key_code: i32, context: &MenuContext) -> Option<MenuAction> {
+        let screen = self.top()?;
+        let mut items = self.items(screen, context);
+        let page = self.stack.last_mut()?;
+        match key_code {
+            ControllerState::KEYCODE_UP => {
+                page.selected = page.selected.checked_sub(1).unwrap_or(items.len() - 1);
+            }
+            ControllerState::KEYCODE_DOWN => page.selected = (page.selected + 1) % items.len(),
+            Self::KEYCODE_RETURN => {
+                let index = page.selected.min(items.len() - 1);
+                return Some(items.swap_remove(index).action);
+            }
+            Self::KEYCODE_ESCAPE => {
+                return match screen {
+                    // don't lose the session on a mis-press
+                    Screen::MainMenu | Screen::LevelComplete | Screen::Death => None,
+                    Screen::Pause => Some(MenuAction::Resume),
+                    Screen::LevelSelect | Screen::Options => Some(MenuAction::Back),
+                };
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn items(&self, screen: Screen, context: &MenuContext) -> Vec<MenuItem> {
+        let mut items = Vec::new();
+        match screen {
+            Screen::MainMenu => {
+                if context.has_session {
+                    items.push(MenuItem::new("Continue", MenuAction::Resume));
+                }
+                items.push(MenuItem::new("New game", MenuAction::NewGame));
+                items.push(MenuItem::new(
+                    "Select level",
+                    MenuAction::Open(Screen::LevelSelect),
+                ));
+                items.push(MenuItem::new("Options", MenuAction::Open(Screen::Options)));
+                items.push(MenuItem::new("Quit", MenuAction::Quit));
+            }
+            Screen::LevelSelect if context.episode.is_some() => {
+                let levels = context.episode.map(|episode| episode.levels.as_slice());
+                for (i, level) in levels.unwrap_or_default().iter().enumerate() {
+                    items.push(MenuItem::new(
+                        format!("{}. {}", i + 1, level.title()),
+                        MenuAction::StartEpisodeLevel(i),
+                    ));
+                }
+                items.push(MenuItem::new("Back", MenuAction::Back));
+            }
+            Screen::LevelSelect => {
+                for level in &self.levels {
+                    let name = Path::new(level)
+                        .file_stem()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_else(|| level.clone());
+                    items.push(MenuItem::new(name, MenuAction::StartLevel(level.clone())));
+                }
+                items.push(MenuItem::new("Back", MenuAction::Back));
+            }
+            Screen::Pause => {
+                items.push(MenuItem::new("Resume", MenuAction::Resume));
+                items.push(MenuItem::new("Restart level", MenuAction::Restart));
+                items.push(MenuItem::new("Options", MenuAction::Open(Screen::Options)));
+                items.push(MenuItem::new("Quit to menu", MenuAction::MainMenu));
+            }
+            Screen::Options => {
+                let settings = context.settings;
+                let controller_state = context.controller_state;
+                let quality = settings.scene.quality;
+                let next_quality = quality.higher().unwrap_or(Quality::Low);
+                items.push(MenuItem::new(
+                    format!("Quality: {quality}"),
+                    MenuAction::SetValue("scene.quality", format!("'{next_quality}'")),
+                ));
+                let flags = [
+                    (
+                        "Dynamic resolution",
+                        "scene.dynamic_resolution",
+                        settings.scene.dynamic_resolution,
+                    ),
+                    ("Show FPS", "hud.show_fps", settings.hud.show_fps),
+                    ("Crosshair", "hud.crosshair", settings.hud.crosshair),
+                ];
+                for (label, key, value) in flags {
+                    items.push(MenuItem::new(
+                        format!("{label}: {}", on_off(value)),
+                        MenuAction::SetValue(key, (!value).to_string()),
+                    ));
+                }
+                items.push(MenuItem::new(
+                    format!("Minimap: {}", on_off(controller_state.minimap_visible)),
+                    MenuAction::ToggleMinimap,
+                ));
+                items.push(MenuItem::new(
+                    format!("Fullscreen: {}", on_off(controller_state.fullscreen)),
+                    MenuAction::ToggleFullscreen,
+                ));
+                items.push(MenuItem::new("Back", MenuAction::Back));
+            }
+            Screen::Death => {
+                items.push(MenuItem::new("Respawn", MenuAction::Restart));
+                items.push(MenuItem::new("Main menu", MenuAction::MainMenu));
+            }
+            Screen::LevelComplete => {
+                if let Some(title) = context.next_level() {
+                    items.push(MenuItem::new(
+                        format!("Next: {title}"),
+                        MenuAction::NextLevel,
+                    ));
+                }
+                items.push(MenuItem::new("Restart level", MenuAction::Restart));
+                items.push(MenuItem::new(
+                    "Select level",
+                    MenuAction::Open(Screen::LevelSelect),
+                ));
+                items.push(MenuItem::new("Main menu", MenuAction::MainMenu));
+            }
+        }
+        items
+    }
+
+    pub fn draw(
+        &self,
+        commands: &mut Vec<DrawCommand>,
+        screen_size: ScreenSize,
+        context: &MenuContext,
+    ) {
+        let Some(page) = self.stack.last() else {
+            return;
+        };
+        let screen = page.screen;
+        let items = self.items(screen, context);
+        let mut lines = Vec::new();
+        if screen == Screen::LevelComplete {
+            lines.push(context.settings.level.title());
+            let seconds = context.level_time as u32;
+            lines.push(format!("Time {}:{:02}", seconds / 60, seconds % 60));
+            lines.extend(context.stats.summary());
+            if context.episode_level.is_some() && context.next_level().is_none() {
+                if let Some(episode) = context.episode {
+                    lines.push(format!("{} complete", episode.name));
+                }
+            }
+        }
+        let line_height = (GLYPH_SIZE * ITEM_SCALE) as i32 + 8;
+        let title_height = (GLYPH_SIZE * TITLE_SCALE) as i32 + 16;
+        let content_width = items
+            .iter()
+            .map(|item| text_width(&item.label, ITEM_SCALE) + text_width("> ", ITEM_SCALE))
+            .chain(lines.iter().map(|line| text_width(line, ITEM_SCALE)))
+            .chain([text_width(screen.title(), TITLE_SCALE)])
+            .max()
+            .unwrap_or_default() as i32;
+        let content_height = title_height + line_height * (lines.len() + items.len()) as i32;
+        let ScreenSize { width, height } = screen_size;
+        let x = (width as i32 - content_width) / 2;
+        let mut y = (height as i32 - content_height) / 2;
+        // backdrop
+        commands.push(DrawCommand::ColorRGB(10, 10, 20));
+        if screen.is_opaque() {
+            commands.push(DrawCommand::Rectangle {
+                x: 0,
+                y: 0,
+                w: width,
+                h: height,
+                fill: true,
+            });
+        } else {
+            let rect = (
+                x - PADDING,
+                y - PADDING,
+                (content_width + 2 * PADDING) as u32,
+                (content_height + 2 * PADDING) as u32,
+            );
+            commands.push(DrawCommand::Rectangle {
+                x: rect.0,
+                y: rect.1,
+                w: rect.2,
+                h: rect.3,
+                fill: true,
+            });
+            commands.push(DrawCommand::ColorRGB(120, 120, 200));
+            commands.push(DrawCommand::Rectangle {
+                x: rect.0,
+                y: rect.1,
+                w: rect.2,
+                h: rect.3,
+                fill: false,
+            });
+        }
+        commands.push(DrawCommand::ColorRGB(255, 200, 60));
+        let title_x = (width as i32 - text_width(screen.title(), TITLE_SCALE) as i32) / 2;
+        draw_text(commands, screen.title(), title_x, y, TITLE_SCALE);
+        y += title_height;
+        commands.push(DrawCommand::ColorRGB(180, 180, 180));
+        for line in &lines {
+            draw_text(commands, line, x, y, ITEM_SCALE);
+            y += line_height;
+        }
+        let selected = page.selected.min(items.len() - 1);
+        for (i, item) in items.iter().enumerate() {
+            let text = if i == selected {
+                commands.push(DrawCommand::ColorRGB(255, 255, 0));
+                format!("> {}", item.label)
+            } else {
+                commands.push(DrawCommand::ColorRGB(200, 200, 200));
+                format!("  {}", item.label)
+            };
+            draw_text(commands, &text, x, y, ITEM_SCALE);
+            y += line_height;
+        }
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Returns maps placed next to the `map` file sorted by name
+pub fn find_levels(map: &str) -> Vec<String> {
+    let dir = Path::new(map)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![map.to_string()];
+    };
+    let mut levels = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pbm"))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    if levels.is_empty() {
+        levels.push(map.to_string());
+    }
+    levels.sort();
+    levels
+}
@@ -0,0 +1,268 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::{
+    automap::ExploredCells,
+    common::{DrawCommand, Float, Float2d, ScreenSize},
+    control::ControllerState,
+    player::Player,
+    raycaster::RayCaster,
+    settings::{MinimapCorner, MinimapSettings},
+    walls::Walls,
+};
+
+const MIN_TILE_SIZE: Float = 2.0;
+const MAX_TILE_SIZE: Float = 64.0;
+// zoom factor change per second
+const ZOOM_SPEED: Float = 2.0;
+// draw every n-th ray only
+const RAYS_STEP: usize = 8;
+
+pub struct Minimap {
+    opts: MinimapSettings,
+    tile_size: Float,
+    // frame position
+    x: i32,
+    y: i32,
+}
+
+impl Minimap {
+    pub fn new(opts: MinimapSettings, tile_size: usize, screen_size: ScreenSize) -> Self {
+        let mut minimap = Self {
+            opts,
+            tile_size: tile_size as Float,
+            x: 0,
+            y: 0,
+        };
+        minimap.resize(screen_size);
+        minimap
+    }
+
+    pub fn set_settings(&mut self, opts: MinimapSettings, screen_size: ScreenSize) {
+        self.opts = opts;
+        self.resize(screen_size);
+    }
+
+    pub fn resize(&mut self, screen_size: ScreenSize) {
+        let size = self.opts.size as i32;
+        let margin = self.opts.margin as i32;
+        let (width, height) = (screen_size.width as i32, screen_size.height as i32);
+        let (x, y) = match self.opts.corner {
+            MinimapCorner::TopLeft => (margin, margin),
+            MinimapCorner::TopRight => (width - size - margin, margin),
+            MinimapCorner::BottomLeft => (margin, height - size - margin),
+            MinimapCorner::BottomRight => (width - size - margin, height - size - margin),
+        };
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn update(&mut self, delta_time: Float, controller_state: &ControllerState) {
+        let factor = 1.0 + ZOOM_SPEED * delta_time;
+        if controller_state.zoom_in_pressed {
+            self.tile_size *= factor;
+        }
+        if controller_state.zoom_out_pressed {
+            self.tile_size /= factor;
+        }
+        self.tile_size = self.tile_size.clamp(MIN_TILE_SIZE, MAX_TILE_SIZE);
+    }
+
+    pub fn draw(
+        &self,
+        commands: &mut Vec<DrawCommand>,
+        walls: &Walls,
+        explored: &ExploredCells,
+        player: &Player,
+        ray_caster: &RayCaster,
+    ) {
+        let size = self.opts.size;
+        commands.push(DrawCommand::ClipRect {
+            x: self.x,
+            y: self.y,
+            w: size,
+            h: size,
+        });
+        commands.push(DrawCommand::ColorRGB(20, 20, 20));
+        commands.push(DrawCommand::Rectangle {
+            x: self.x,
+            y: self.y,
+            w: size,
+            h: size,
+            fill: true,
+        });
+        let transform = self.transform(walls, player);
+        self.draw_walls(commands, walls, explored, &transform);
+        if self.opts.show_rays {
+            commands.push(DrawCommand::ColorRGB(90, 90, 40));
+            let (x1, y1) = transform.apply(player.pos());
+            for hit in ray_caster.hits().step_by(RAYS_STEP) {
+                let (x2, y2) = transform.apply(hit);
+                commands.push(DrawCommand::Line { x1, y1, x2, y2 });
+            }
+        }
+        draw_player_marker(commands, player, &transform);
+        commands.push(DrawCommand::ResetClip);
+        commands.push(DrawCommand::ColorRGB(200, 200, 200));
+        commands.push(DrawCommand::Rectangle {
+            x: self.x,
+            y: self.y,
+            w: size,
+            h: size,
+            fill: false,
+        });
+    }
+
+    fn transform(&self, walls: &Walls, player: &Player) -> MapTransform {
+        let half_size = 0.5 * self.opts.size as Float;
+        let center = if self.opts.player_centered {
+            player.pos()
+        } else {
+            let (cols, rows) = walls.size();
+            Float2d::new(0.5 * cols as Float, 0.5 * rows as Float)
+        };
+        // player looks up when the map rotates
+        let angle = if self.opts.rotate {
+            -player.angle() - FRAC_PI_2
+        } else {
+            0.0
+        };
+        MapTransform::new(
+            center,
+            angle,
+            self.tile_size,
+            Float2d::new(self.x as Float + half_size, self.y as Float + half_size),
+        )
+    }
+
+    fn draw_walls(
+        &self,
+        commands: &mut Vec<DrawCommand>,
+        walls: &Walls,
+        explored: &ExploredCells,
+        transform: &MapTransform,
+    ) {
+        let (cols, rows) = walls.size();
+        // tiles within the frame's circumcircle
+        let radius = (0.75 * self.opts.size as Float / self.tile_size).ceil() as i32;
+        let (center_col, center_row) = (transform.center.x as i32, transform.center.y as i32);
+        let col_range = (center_col - radius).max(0)..(center_col + radius + 1).min(cols as i32);
+        let row_range = (center_row - radius).max(0)..(center_row + radius + 1).min(rows as i32);
+        commands.push(DrawCommand::ColorRGB(255, 255, 255));
+        for row in row_range {
+            for col in col_range.clone() {
+                let (col, row) = (col as usize, row as usize);
+                if walls.tile(col, row) == 0 || !explored.is_explored(col, row) {
+                    continue;
+                }
+                if let Some((start, end)) = walls.segment(col as i32, row as i32) {
+                    let (x1, y1) = transform.apply(start);
+                    let (x2, y2) = transform.apply(end);
+                    commands.push(DrawCommand::Line { x1, y1, x2, y2 });
+                    continue;
+                }
+                let (x, y) = (col as Float, row as Float);
+                if self.opts.rotate {
+                    Self::fill_quad(
+                        commands,
+                        [
+                            transform.apply(Float2d::new(x, y)),
+                            transform.apply(Float2d::new(x + 1.0, y)),
+                            transform.apply(Float2d::new(x + 1.0, y + 1.0)),
+                            transform.apply(Float2d::new(x, y + 1.0)),
+                        ],
+                        self.tile_size.ceil() as usize,
+                    );
+                } else {
+                    let (x1, y1) = transform.apply(Float2d::new(x, y));
+                    let (x2, y2) = transform.apply(Float2d::new(x + 1.0, y + 1.0));
+                    commands.push(DrawCommand::Rectangle {
+                        x: x1,
+                        y: y1,
+                        w: (x2 - x1) as u32,
+                        h: (y2 - y1) as u32,
+                        fill: true,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Fills quad with lines between its opposite edges
+    fn fill_quad(commands: &mut Vec<DrawCommand>, corners: [(i32, i32); 4], steps: usize) {
+        let [a, b, c, d] = corners.map(|(x, y)| (x as Float, y as Float));
+        let lerp = |p: (Float, Float), q: (Float, Float), t: Float| {
+            (p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t)
+        };
+        let steps = 2 * steps.max(1);
+        for i in 0..=steps {
+            let t = i as Float / steps as Float;
+            let (x1, y1) = lerp(a, d, t);
+            let (x2, y2) = lerp(b, c, t);
+            commands.push(DrawCommand::Line {
+                x1: x1 as i32,
+                y1: y1 as i32,
+                x2: x2 as i32,
+                y2: y2 as i32,
+            });
+        }
+    }
+}
+
+/// Draws player's position and view direction
+pub fn draw_player_marker(
+    commands: &mut Vec<DrawCommand>,
+    player: &Player,
+    transform: &MapTransform,
+) {
+    let (x, y) = transform.apply(player.pos());
+    let size = 6;
+    commands.push(DrawCommand::ColorRGB(255, 128, 128));
+    commands.push(DrawCommand::Rectangle {
+        x: x - size / 2,
+        y: y - size / 2,
+        w: size as u32,
+        h: size as u32,
+        fill: true,
+    });
+    let length = 2.0;
+    let direction = Float2d::new(length * player.angle().cos(), length * player.angle().sin());
+    let (x2, y2) = transform.apply(player.pos() + direction);
+    commands.push(DrawCommand::Line {
+        x1: x,
+        y1: y,
+        x2,
+        y2,
+    });
+}
+
+/// Maps world coordinates to the screen: `center` point goes to `origin`,
+/// the map is rotated by `angle` and scaled to `scale` pixels per tile
+pub struct MapTransform {
+    pub center: Float2d,
+    sin_a: Float,
+    cos_a: Float,
+    scale: Float,
+    origin: Float2d,
+}
+
+impl MapTransform {
+    pub fn new(center: Float2d, angle: Float, scale: Float, origin: Float2d) -> Self {
+        Self {
+            center,
+            sin_a: angle.sin(),
+            cos_a: angle.cos(),
+            scale,
+            origin,
+        }
+    }
+
+    pub fn apply(&self, point: Float2d) -> (i32, i32) {
+        let Float2d { x, y } = point - self.center;
+        let x_rot = x * self.cos_a - y * self.sin_a;
+        let y_rot = x * self.sin_a + y * self.cos_a;
+        (
+            (self.origin.x + x_rot * self.scale).round() as i32,
+            (self.origin.y + y_rot * self.scale).round() as i32,
+        )
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{common::Float, walls::Walls};
+
+/// Tile as (column, row)
+pub type Tile = (i32, i32);
+
+// path queries allowed per frame, the rest wait for the next frames
+const SEARCHES_PER_FRAME: usize = 4;
+// nodes expanded by a single search at most
+const MAX_EXPANDED_NODES: usize = 4096;
+const MAX_CACHED_PATHS: usize = 256;
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+
+#[derive(PartialEq)]
+struct Node {
+    tile: Tile,
+    cost: Float,
+    estimate: Float,
+}
+
+impl Eq for Node {}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed to make the max-heap pop the cheapest node
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Octile distance, exact for 8-directional moves without obstacles
+fn heuristic(a: Tile, b: Tile) -> Float {
+    let dx = (a.0 - b.0).abs() as Float;
+    let dy = (a.1 - b.1).abs() as Float;
+    dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+}
+
+/// A* search over passable tiles, diagonal moves can't cut wall corners.
+/// Returns tiles from `start` (excluded) to `goal` (included)
+pub fn find_path(walls: &Walls, start: Tile, goal: Tile, max_nodes: usize) -> Option<Vec<Tile>> {
+    if !walls.is_passable(goal.0, goal.1) {
+        return None;
+    }
+    let mut open = BinaryHeap::new();
+    let mut came_from = HashMap::<Tile, Tile>::new();
+    let mut costs = HashMap::<Tile, Float>::new();
+    open.push(Node {
+        tile: start,
+        cost: 0.0,
+        estimate: heuristic(start, goal),
+    });
+    costs.insert(start, 0.0);
+    let mut expanded = 0;
+    while let Some(Node { tile, cost, .. }) = open.pop() {
+        if tile == goal {
+            let mut path = vec![tile];
+            let mut current = tile;
+            while let Some(prev) = came_from.get(&current) {
+                if *prev == start {
+                    break;
+                }
+                path.push(*prev);
+                current = *prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        // skip outdated heap entries
+        if costs.get(&tile).is_some_and(|best| cost > *best) {
+            continue;
+        }
+        expanded += 1;
+        if expanded > max_nodes {
+            return None;
+        }
+        for (dx, dy) in NEIGHBOURS {
+            let next = (tile.0 + dx, tile.1 + dy);
+            if !walls.is_passable(next.0, next.1) {
+                continue;
+            }
+            let is_diagonal = dx != 0 && dy != 0;
+            if is_diagonal
+                && !(walls.is_passable(tile.0 + dx, tile.1)
+                    && walls.is_passable(tile.0, tile.1 + dy))
+            {
+                continue;
+            }
+            let step = if is_diagonal {
+                std::f32::consts::SQRT_2
+            } else {
+                1.0
+            };
+            let next_cost = cost + step;
+            if costs.get(&next).is_some_and(|best| *best <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, tile);
+            open.push(Node {
+                tile: next,
+                cost: next_cost,
+                estimate: next_cost + heuristic(next, goal),
+            });
+        }
+    }
+    None
+}
+
+/// Caches path queries and limits the number of searches per frame
+#[derive(Default)]
+pub struct Pathfinder {
+    cache: HashMap<(Tile, Tile), Option<Vec<Tile>>>,
+    /// walls revision the cached paths were found for
+    revision: u64,
+    searches_left: usize,
+}
+
+impl Pathfinder {
+    pub fn begin_frame(&mut self) {
+        self.searches_left = SEARCHES_PER_FRAME;
+    }
+
+    /// Returns `None` if the search budget is exhausted, `Some(None)` if there is no path
+    pub fn find(&mut self, walls: &Walls, start: Tile, goal: Tile) -> Option<Option<Vec<Tile>>> {
+        if self.revision != walls.revision() {
+            self.cache.clear();
+            self.revision = walls.revision();
+        }
+        if let Some(path) = self.cache.get(&(start, goal)) {
+            return Some(path.clone());
+        }
+        if self.searches_left == 0 {
+            return None;
+        }
+        self.searches_left -= 1;
+        if self.cache.len() >= MAX_CACHED_PATHS {
+            self.cache.clear();
+        }
+        let path = find_path(walls, start, goal, MAX_EXPANDED_NODES);
+        self.cache.insert((start, goal), path.clone());
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Open room split by a wall in the middle row
+    fn room() -> Walls {
+        Walls::with_rows(
+            &["1111111", "1000001", "1011101", "1000001", "1111111"],
+            &[],
+        )
+    }
+
+    fn assert_connected(path: &[Tile], start: Tile, goal: Tile) {
+        assert_eq!(path.last(), Some(&goal));
+        let mut previous = start;
+        for tile in path {
+            let (dx, dy) = (tile.0 - previous.0, tile.1 - previous.1);
+            assert!(dx.abs() <= 1 && dy.abs() <= 1, "{previous:?} -> {tile:?}");
+            previous = *tile;
+        }
+    }
+
+    #[test]
+    fn path_goes_around_walls() {
+        let walls = room();
+        let (start, goal) = ((1, 2), (5, 2));
+        let path = find_path(&walls, start, goal, usize::MAX).unwrap();
+        assert_connected(&path, start, goal);
+        // diagonal steps around the wall's ends would cut its corners
+        assert_eq!(path.len(), 6);
+        assert!(path.iter().all(|(col, row)| walls.is_passable(*col, *row)));
+    }
+
+    #[test]
+    fn diagonal_moves_are_taken_in_the_open() {
+        let walls = Walls::with_rows(&["11111", "10001", "10001", "10001", "11111"], &[]);
+        let path = find_path(&walls, (1, 1), (3, 3), usize::MAX);
+        assert_eq!(path, Some(vec![(2, 2), (3, 3)]));
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        let walls = Walls::with_rows(&["11111", "10101", "11111"], &[]);
+        assert_eq!(find_path(&walls, (1, 1), (3, 1), usize::MAX), None);
+        assert_eq!(find_path(&walls, (1, 1), (2, 1), usize::MAX), None);
+        assert_eq!(find_path(&walls, (1, 1), (9, 9), usize::MAX), None);
+        let walls = room();
+        assert_eq!(find_path(&walls, (1, 2), (5, 2), 2), None);
+    }
+
+    #[test]
+    fn cached_paths_are_dropped_on_map_changes() {
+        let mut walls = room();
+        let mut pathfinder = Pathfinder::default();
+        pathfinder.begin_frame();
+        let path = pathfinder.find(&walls, (1, 1), (5, 1)).unwrap().unwrap();
+        assert_eq!(path.len(), 4);
+        walls.set_tile(3, 1, 1);
+        let path = pathfinder.find(&walls, (1, 1), (5, 1)).unwrap().unwrap();
+        assert!(!path.contains(&(3, 1)));
+    }
+}
@@ -1,28 +1,179 @@
 use std::f32::consts::PI;
 
 use crate::{
-    common::{DrawCommand, Float, Float2d},
+    common::{Float, Float2d},
     control::ControllerState,
+    entity::Sprite,
+    inventory::Inventory,
     settings::PlayerSettings,
-    walls::Walls,
 };
 
+pub const MAX_HEALTH: i32 = 200;
+pub const MAX_ARMOR: i32 = 200;
+const TEXTURE_ID_BODY: i32 = 105;
+// health and armor pickups don't raise stats above these values
+const PICKUP_HEALTH_LIMIT: i32 = 100;
+const PICKUP_ARMOR_LIMIT: i32 = 100;
+// part of the damage absorbed by armor
+const ARMOR_ABSORPTION: Float = 0.5;
+// seconds
+const HURT_FLASH_TIME: Float = 0.4;
+const DEATH_DROP_TIME: Float = 1.0;
+// relative to the wall height
+const EYE_HEIGHT: Float = 0.5;
+const DEAD_EYE_HEIGHT: Float = 0.1;
+
 #[derive(Default)]
 pub struct Player {
     position: Float2d,
     angle: Float,
     movement_speed: Float,
     rotation_speed: Float,
-    tile_size: Float,
+    noclip: bool,
+    health: i32,
+    armor: i32,
+    // fractional damage over time not applied yet
+    pending_damage: Float,
+    hurt_flash: Float,
+    /// seconds since death
+    dead_time: Option<Float>,
+    inventory: Inventory,
 }
 
 impl Player {
-    pub fn new(settings: &PlayerSettings, tile_size: usize) -> Self {
-        Self {
-            movement_speed: settings.player_movement_speed,
-            rotation_speed: settings.player_rotation_speed,
-            tile_size: tile_size as Float,
-            ..Self::default()
+    /// Collision radius used against entities
+    pub const RADIUS: Float = 0.2;
+
+    pub fn new(settings: &PlayerSettings) -> Self {
+        let mut player = Self::default();
+        player.set_settings(settings);
+        player
+    }
+
+    pub fn set_settings(&mut self, settings: &PlayerSettings) {
+        self.movement_speed = settings.player_movement_speed;
+        self.rotation_speed = settings.player_rotation_speed;
+    }
+
+    /// Toggles walking through walls, returns the new state
+    pub fn toggle_noclip(&mut self) -> bool {
+        self.noclip = !self.noclip;
+        self.noclip
+    }
+
+    /// Resets health, armor, inventory and death state
+    pub fn reset_stats(&mut self, health: i32, armor: i32) {
+        self.inventory = Inventory::default();
+        self.health = health;
+        self.armor = armor;
+        self.pending_damage = 0.0;
+        self.hurt_flash = 0.0;
+        self.dead_time = None;
+    }
+
+    pub fn health(&self) -> i32 {
+        self.health
+    }
+
+    pub fn armor(&self) -> i32 {
+        self.armor
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
+    }
+
+    pub fn set_inventory(&mut self, inventory: Inventory) {
+        self.inventory = inventory;
+    }
+
+    /// Returns false if the health is already at the pickup limit
+    pub fn heal(&mut self, amount: i32) -> bool {
+        if self.is_dead() || self.health >= PICKUP_HEALTH_LIMIT {
+            return false;
+        }
+        self.health = (self.health + amount).min(PICKUP_HEALTH_LIMIT);
+        true
+    }
+
+    /// Returns false if the armor is already at the pickup limit
+    pub fn add_armor(&mut self, amount: i32) -> bool {
+        if self.armor >= PICKUP_ARMOR_LIMIT {
+            return false;
+        }
+        self.armor = (self.armor + amount).min(PICKUP_ARMOR_LIMIT);
+        true
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.dead_time.is_some()
+    }
+
+    /// Seconds since death
+    pub fn dead_time(&self) -> Option<Float> {
+        self.dead_time
+    }
+
+    /// Applies damage partially absorbed by armor, returns true if the player dies
+    pub fn take_damage(&mut self, amount: i32) -> bool {
+        if self.is_dead() || amount <= 0 {
+            return false;
+        }
+        let absorbed = ((amount as Float * ARMOR_ABSORPTION) as i32).min(self.armor);
+        self.armor -= absorbed;
+        self.health -= amount - absorbed;
+        self.hurt_flash = HURT_FLASH_TIME;
+        if self.health > 0 {
+            return false;
+        }
+        self.health = 0;
+        self.dead_time = Some(0.0);
+        true
+    }
+
+    /// Accumulates damage over time, returns true if the player dies
+    pub fn take_damage_over_time(&mut self, damage_per_second: Float, delta_time: Float) -> bool {
+        self.pending_damage += damage_per_second * delta_time;
+        let amount = self.pending_damage.floor();
+        self.pending_damage -= amount;
+        self.take_damage(amount as i32)
+    }
+
+    /// Hurt flash intensity in range 0..1
+    pub fn hurt_flash(&self) -> Float {
+        self.hurt_flash / HURT_FLASH_TIME
+    }
+
+    /// Camera height relative to the wall height, drops on death
+    pub fn eye_height(&self) -> Float {
+        match self.dead_time {
+            Some(time) => {
+                let progress = (time / DEATH_DROP_TIME).min(1.0);
+                EYE_HEIGHT + (DEAD_EYE_HEIGHT - EYE_HEIGHT) * progress
+            }
+            None => EYE_HEIGHT,
+        }
+    }
+
+    /// Player's body seen in mirrors, it's too close to the camera to be drawn otherwise
+    pub fn sprite(&self) -> Sprite {
+        Sprite {
+            texture_id: TEXTURE_ID_BODY,
+            color: (40, 90, 200),
+            height: 0.8,
+            elevation: 0.0,
+        }
+    }
+
+    /// Updates timers of the stats, called even if the player can't move
+    pub fn update_status(&mut self, delta_time: Float) {
+        self.hurt_flash = (self.hurt_flash - delta_time).max(0.0);
+        if let Some(time) = self.dead_time.as_mut() {
+            *time += delta_time;
         }
     }
 
@@ -31,7 +182,15 @@ impl Player {
         self.angle = angle;
     }
 
-    pub fn update(&mut self, delta_time: Float, controller_state: &ControllerState, map: &Walls) {
+    /// Moves the player unless `is_blocked` returns true for the new position,
+    /// `teleport` returns the position and the turn angle if the move passes a portal
+    pub fn update(
+        &mut self,
+        delta_time: Float,
+        controller_state: &ControllerState,
+        is_blocked: impl Fn(Float2d) -> bool,
+        teleport: impl Fn(Float2d, Float2d) -> Option<(Float2d, Float)>,
+    ) {
         let sin_a = self.angle.sin();
         let cos_a = self.angle.cos();
         let (mut dx, mut dy) = (0.0, 0.0);
@@ -55,12 +214,20 @@ impl Player {
             dx = -dist_sin;
             dy = dist_cos;
         }
-        [(dx, 0.0), (0.0, dy)].iter().for_each(|(dx, dy)| {
-            let position = self.position + Float2d::new(*dx, *dy);
-            if !map.has_collision(position) {
-                self.position = position;
+        for (dx, dy) in [(dx, 0.0), (0.0, dy)] {
+            let position = self.position + Float2d::new(dx, dy);
+            if !self.noclip && is_blocked(position) {
+                continue;
             }
-        });
+            let Some((position, turn)) = teleport(self.position, position) else {
+                self.position = position;
+                continue;
+            };
+            // the rest of the move was meant for the old direction
+            self.position = position;
+            self.angle += turn;
+            break;
+        }
 
         // TODO: add mouse sensitivity config parameter
         // self.angle += controller_state.mouse_x_relative as Float * delta_time;
@@ -74,33 +241,6 @@ impl Player {
         self.angle %= 2.0 * PI;
     }
 
-    pub fn draw(&self, commands: &mut Vec<DrawCommand>) {
-        commands.push(DrawCommand::ColorRGB(255, 128, 128));
-        let size = 10;
-        let (x, y) = (
-            (self.position.x * self.tile_size) as i32,
-            (self.position.y * self.tile_size) as i32,
-        );
-        let rect = DrawCommand::Rectangle {
-            x: x - size / 2,
-            y: y - size / 2,
-            w: size as u32,
-            h: size as u32,
-            fill: true,
-        };
-        commands.push(rect);
-
-        let length = 3.0 * self.tile_size;
-        let line = DrawCommand::Line {
-            x1: x,
-            y1: y,
-            x2: x + (length * self.angle.cos()) as i32,
-            y2: y + (length * self.angle.sin()) as i32,
-        };
-        commands.push(line);
-        commands.push(DrawCommand::ColorRGB(0, 0, 0));
-    }
-
     pub fn pos(&self) -> Float2d {
         self.position
     }
@@ -0,0 +1,159 @@
+use crate::{
+    common::{Float, Float2d},
+    walls::Walls,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayStep {
+    pub col: i32,
+    pub row: i32,
+    /// distance from the ray origin to the entry point
+    pub depth: Float,
+}
+
+/// Walks tiles along the ray (DDA), yields every tile the ray enters
+pub struct GridRay {
+    col: i32,
+    row: i32,
+    step_col: i32,
+    step_row: i32,
+    // depth to the next vertical/horizontal grid line
+    next_x: Float,
+    next_y: Float,
+    // depth between grid lines
+    delta_x: Float,
+    delta_y: Float,
+}
+
+impl GridRay {
+    pub fn new(origin: Float2d, angle: Float) -> Self {
+        let (cos_a, sin_a) = (angle.cos(), angle.sin());
+        let (col, row) = (origin.x.floor() as i32, origin.y.floor() as i32);
+        let delta_x = (1.0 / cos_a).abs();
+        let delta_y = (1.0 / sin_a).abs();
+        let (step_col, next_x) = if cos_a > 0.0 {
+            (1, (col as Float + 1.0 - origin.x) * delta_x)
+        } else {
+            (-1, (origin.x - col as Float) * delta_x)
+        };
+        let (step_row, next_y) = if sin_a > 0.0 {
+            (1, (row as Float + 1.0 - origin.y) * delta_y)
+        } else {
+            (-1, (origin.y - row as Float) * delta_y)
+        };
+        Self {
+            col,
+            row,
+            step_col,
+            step_row,
+            next_x,
+            next_y,
+            delta_x,
+            delta_y,
+        }
+    }
+
+    /// Tile containing the ray origin
+    pub fn origin_tile(&self) -> (i32, i32) {
+        (self.col, self.row)
+    }
+}
+
+impl Iterator for GridRay {
+    type Item = RayStep;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let depth = if self.next_x < self.next_y {
+            self.col += self.step_col;
+            let depth = self.next_x;
+            self.next_x += self.delta_x;
+            depth
+        } else {
+            self.row += self.step_row;
+            let depth = self.next_y;
+            self.next_y += self.delta_y;
+            depth
+        };
+        Some(RayStep {
+            col: self.col,
+            row: self.row,
+            depth,
+        })
+    }
+}
+
+/// Returns depth where the ray hits the wall in the tile, thin walls may be missed
+fn hit_depth(walls: &Walls, step: RayStep, origin: Float2d, angle: Float) -> Option<Float> {
+    if walls.segment(step.col, step.row).is_none() {
+        return Some(step.depth);
+    }
+    let direction = Float2d::new(angle.cos(), angle.sin());
+    walls
+        .intersect(step.col, step.row, origin, direction)
+        .map(|(depth, _)| depth)
+}
+
+/// Steps of the ray including its origin tile which may contain a thin wall
+fn steps(origin: Float2d, angle: Float) -> impl Iterator<Item = RayStep> {
+    let ray = GridRay::new(origin, angle);
+    let (col, row) = ray.origin_tile();
+    std::iter::once(RayStep {
+        col,
+        row,
+        depth: 0.0,
+    })
+    .chain(ray)
+}
+
+/// Returns distance to the first wall stopping shots along the ray within `max_depth`
+pub fn cast(walls: &Walls, origin: Float2d, angle: Float, max_depth: Float) -> Option<Float> {
+    if let Some(sectors) = walls.sectors() {
+        return sectors.cast(origin, angle, max_depth);
+    }
+    let origin_tile = (origin.x.floor() as i32, origin.y.floor() as i32);
+    for step in steps(origin, angle) {
+        if step.depth > max_depth {
+            return None;
+        }
+        // whole cell walls are never hit from the inside
+        let is_origin = (step.col, step.row) == origin_tile;
+        if !walls.blocks_shots(step.col, step.row)
+            || is_origin && walls.segment(step.col, step.row).is_none()
+        {
+            continue;
+        }
+        let depth = hit_depth(walls, step, origin, angle);
+        if let Some(depth) = depth.filter(|depth| *depth <= max_depth) {
+            return Some(depth);
+        }
+    }
+    None
+}
+
+/// Checks that the segment between the points doesn't cross any opaque wall
+pub fn has_line_of_sight(walls: &Walls, from: Float2d, to: Float2d) -> bool {
+    let Float2d { x, y } = to - from;
+    let distance = x.hypot(y);
+    let angle = y.atan2(x);
+    if let Some(sectors) = walls.sectors() {
+        return sectors
+            .cast(from, angle, distance)
+            .is_none_or(|depth| depth >= distance);
+    }
+    let origin_tile = (from.x.floor() as i32, from.y.floor() as i32);
+    for step in steps(from, angle) {
+        if step.depth >= distance {
+            return true;
+        }
+        let is_origin = (step.col, step.row) == origin_tile;
+        if !walls.blocks_sight(step.col, step.row)
+            || is_origin && walls.segment(step.col, step.row).is_none()
+        {
+            continue;
+        }
+        if hit_depth(walls, step, from, angle).is_some_and(|depth| depth < distance) {
+            return false;
+        }
+    }
+    true
+}
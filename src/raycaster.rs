@@ -1,17 +1,132 @@
+use std::{f32::consts::PI, ops::Range};
+
 use crate::{
-    common::{DrawCommand, Float, Float2d},
+    common::{DrawCommand, Float, Float2d, ScreenSize},
+    entity::Sprite,
+    ray::{GridRay, RayStep},
+    sectors::SectorMap,
     settings::SceneSettings,
-    walls::Walls,
+    walls::{rotate, Walls},
 };
 
 const TOL: Float = 1e-5;
-const DEFAULT_TEXTURE_ID: i32 = 1;
+// sprites closer than that aren't drawn
+const MIN_SPRITE_DEPTH: Float = 0.1;
+// top and bottom faces of the walls lower or higher than the eye
+const CAP_COLOR: (u8, u8, u8) = (60, 60, 60);
+// rays start over that far from the mirror or the portal to leave its wall
+const CROSSING_OFFSET: Float = 1e-4;
+// colors of the things seen directly are kept
+const NO_SHADE: (u8, u8, u8) = (255, 255, 255);
 
+/// Wall face hit by a ray
 struct Rect {
     projected_height: Float,
     texture_id: i32,
     texture_offset: Float,
     depth: Float,
+    /// depth where the ray leaves the tile, the top or bottom face is seen in between
+    exit_depth: Float,
+    /// vertical extent in wall units
+    floor: Float,
+    top: Float,
+    /// transparent parts of the texture show walls and sprites behind it
+    is_masked: bool,
+    /// color of the top or bottom face, sector floors and ceilings are faces
+    /// of zero height rects
+    cap_color: (u8, u8, u8),
+    /// tint of the mirrors the wall is seen in
+    shade: (u8, u8, u8),
+}
+
+/// Mirror or portal the ray passed, everything behind it is seen through the view transform
+struct Crossing {
+    depth: Float,
+    /// index of the view mapping the map behind the crossing to the screen
+    view: usize,
+}
+
+/// Isometry moving the part of the map seen in mirrors and portals to where
+/// the straight ray sees it
+#[derive(Clone, Copy)]
+struct ViewTransform {
+    x_axis: Float2d,
+    y_axis: Float2d,
+    origin: Float2d,
+    /// product of the mirrors' tints
+    shade: (u8, u8, u8),
+}
+
+impl Default for ViewTransform {
+    fn default() -> Self {
+        Self {
+            x_axis: Float2d::new(1.0, 0.0),
+            y_axis: Float2d::new(0.0, 1.0),
+            origin: Float2d::default(),
+            shade: NO_SHADE,
+        }
+    }
+}
+
+impl ViewTransform {
+    fn apply(&self, point: Float2d) -> Float2d {
+        self.origin + self.rotate(point)
+    }
+
+    fn rotate(&self, vector: Float2d) -> Float2d {
+        Float2d::new(
+            self.x_axis.x * vector.x + self.y_axis.x * vector.y,
+            self.x_axis.y * vector.x + self.y_axis.y * vector.y,
+        )
+    }
+
+    /// Adds the reflection in the line through the point with the unit normal
+    fn then_mirror(&self, point: Float2d, normal: Float2d, tint: (u8, u8, u8)) -> Self {
+        let mirror = |vector: Float2d| {
+            let dot = 2.0 * vector.dot(&normal);
+            Float2d::new(vector.x - dot * normal.x, vector.y - dot * normal.y)
+        };
+        let shift = 2.0 * point.dot(&normal);
+        Self {
+            x_axis: self.rotate(mirror(Float2d::new(1.0, 0.0))),
+            y_axis: self.rotate(mirror(Float2d::new(0.0, 1.0))),
+            origin: self.apply(Float2d::new(shift * normal.x, shift * normal.y)),
+            shade: multiply(self.shade, tint),
+        }
+    }
+
+    /// Adds the portal moving points by the turn around `from` to `to`
+    fn then_portal(&self, from: Float2d, to: Float2d, turn: Float) -> Self {
+        // the view maps the points back to the entered portal
+        let back = |vector: Float2d| rotate(vector, -turn);
+        Self {
+            x_axis: self.rotate(back(Float2d::new(1.0, 0.0))),
+            y_axis: self.rotate(back(Float2d::new(0.0, 1.0))),
+            origin: self.apply(from - back(to)),
+            shade: self.shade,
+        }
+    }
+
+    fn is_close(&self, other: &Self) -> bool {
+        [
+            self.x_axis - other.x_axis,
+            self.y_axis - other.y_axis,
+            self.origin - other.origin,
+        ]
+        .iter()
+        .all(|delta| delta.x.abs() < 1e-3 && delta.y.abs() < 1e-3)
+    }
+}
+
+/// Walls hit by a single ray, ordered from near to far
+struct Column {
+    /// the first wall blocking the view at the eye level or the farthest point the ray reached
+    hit: Float2d,
+    /// depth of the wall hiding everything behind it
+    depth: Float,
+    rects: Range<usize>,
+    /// mirrors and portals the ray passed ordered by depth
+    crossings: Range<usize>,
 }
 
 #[derive(Default)]
@@ -19,129 +134,562 @@ pub struct RayCaster {
     half_fov: Float,
     screen_distance: Float,
     scale: Float,
+    width: usize,
     height: Float,
+    ray_divisor: usize,
     rays: usize,
     delta_angle: Float,
     max_depth: usize,
+    max_reflections: usize,
+    max_portals: usize,
     rect_buffer: Vec<Rect>,
+    columns: Vec<Column>,
+    crossing_buffer: Vec<Crossing>,
+    /// distinct views seen by the columns
+    views: Vec<ViewTransform>,
+    // view of the last update
+    position: Float2d,
+    angle: Float,
+    /// camera height relative to the wall height
+    eye_height: Float,
 }
 
 impl RayCaster {
-    pub fn new(opts: &SceneSettings) -> Self {
-        let half_fov = 0.5 * opts.fov;
-        let rays = opts.screen_width >> 1;
-        let delta_angle = opts.fov / rays as Float;
+    pub fn new(opts: &SceneSettings, ray_divisor: usize) -> Self {
+        let screen_size = opts.screen_size();
+        let mut ray_caster = Self {
+            max_depth: opts.max_depth,
+            max_reflections: opts.max_reflections,
+            max_portals: opts.max_portals,
+            ray_divisor,
+            eye_height: 0.5,
+            ..Self::default()
+        };
+        ray_caster.resize(&screen_size, opts.horizontal_fov(&screen_size));
+        ray_caster
+    }
 
-        let screen_distance = opts.screen_width as Float * 0.5 * half_fov.tan();
-        let scale = opts.screen_width as Float / rays as Float;
+    /// Recalculates projection for the new screen size and horizontal field of view
+    pub fn resize(&mut self, screen_size: &ScreenSize, fov: Float) {
+        self.half_fov = 0.5 * fov;
+        self.width = screen_size.width as usize;
+        self.height = screen_size.height as Float;
+        self.screen_distance = self.width as Float * 0.5 * self.half_fov.tan();
+        self.set_ray_divisor(self.ray_divisor);
+    }
 
-        Self {
-            half_fov,
-            screen_distance,
-            scale,
-            height: opts.screen_height as Float,
-            rays,
-            delta_angle,
-            max_depth: opts.max_depth,
-            rect_buffer: Vec::with_capacity(rays),
-        }
+    pub fn set_eye_height(&mut self, eye_height: Float) {
+        self.eye_height = eye_height;
     }
 
+    /// Sets the number of screen columns covered by a single ray
+    pub fn set_ray_divisor(&mut self, ray_divisor: usize) {
+        self.ray_divisor = ray_divisor;
+        let rays = (self.width / ray_divisor.max(1)).max(1);
+        self.rays = rays;
+        self.delta_angle = 2.0 * self.half_fov / rays as Float;
+        self.scale = self.width as Float / rays as Float;
+        self.columns.reserve(rays);
+    }
+
+    /// Casts the rays, masked walls and walls lower than the highest one on the map
+    /// don't stop them
     pub fn update(&mut self, pos: Float2d, angle: Float, map: &Walls) {
         self.rect_buffer.clear();
-        // default texture ids
-        let (mut texture_id_vertical, mut texture_id_horizontal) =
-            (DEFAULT_TEXTURE_ID, DEFAULT_TEXTURE_ID);
-        let (tile_x, tile_y) = (pos.x.floor(), pos.y.floor());
+        self.columns.clear();
+        self.crossing_buffer.clear();
+        self.views.clear();
+        self.position = pos;
+        self.angle = angle;
+        if let Some(sectors) = map.sectors() {
+            self.update_sectors(sectors);
+            return;
+        }
+        let max_depth = self.max_depth as Float;
+        let max_height = map.max_height();
         let mut ray_angle = angle - self.half_fov;
         for _ in 0..self.rays {
-            let sin_a = ray_angle.sin();
-            let cos_a = ray_angle.cos();
-            // horizontals
-            let (mut horizontal_y, dy) = if sin_a > 0.0 {
-                (tile_y + 1.0, 1.0)
-            } else {
-                (tile_y - TOL, -1.0)
-            };
-            let mut horizontal_depth = (horizontal_y - pos.y) / sin_a;
-            let mut horizontal_x = pos.x + horizontal_depth * cos_a;
-            let depth_delta = dy / sin_a;
-            let dx = depth_delta * cos_a;
-            for _ in 0..self.max_depth {
-                let point = Float2d::new(horizontal_x, horizontal_y);
-                if map.has_collision(point) {
-                    texture_id_horizontal = map.texture_id(point);
-                    break;
+            // get rid of fishbowl effect
+            let correction = (angle - ray_angle).cos();
+            let first = self.rect_buffer.len();
+            let first_crossing = self.crossing_buffer.len();
+            let mut depth = max_depth;
+            let mut column_depth = Float::MAX;
+            // heights covered by the opaque walls crossing the eye level hit so far,
+            // the walls behind them within these heights are hidden
+            let mut cover = (Float::MAX, Float::MIN);
+            let mut sight_depth = None;
+            // the ray starts over from every mirror and portal,
+            // depths are measured along the whole path
+            let (mut origin, mut direction) = (pos, Float2d::new(ray_angle.cos(), ray_angle.sin()));
+            let mut travelled = 0.0;
+            let mut view = ViewTransform::default();
+            let (mut reflections, mut portals) = (0, 0);
+            'path: loop {
+                let ray = GridRay::new(origin, direction.y.atan2(direction.x));
+                let mut previous = ray.origin_tile();
+                // thin walls of the tile the ray starts in are visible too
+                let origin_step = RayStep {
+                    col: previous.0,
+                    row: previous.1,
+                    depth: 0.0,
+                };
+                let origin_step = map
+                    .segment(origin_step.col, origin_step.row)
+                    .map(|_| origin_step);
+                for step in origin_step.into_iter().chain(ray) {
+                    let step_depth = travelled + step.depth;
+                    // the previous wall ends where the ray enters the next tile
+                    if let Some(rect) = self.rect_buffer[first..].last_mut() {
+                        if rect.exit_depth == Float::MAX {
+                            rect.exit_depth = step_depth * correction;
+                        }
+                    }
+                    if step_depth > max_depth {
+                        break 'path;
+                    }
+                    let mut is_vertical = step.col != previous.0;
+                    previous = (step.col, step.row);
+                    let Some(texture_id) = map.checked_tile(step.col, step.row) else {
+                        depth = step_depth;
+                        break 'path;
+                    };
+                    let sliding = map.intersect_sliding(step.col, step.row, origin, direction);
+                    if texture_id == 0 && sliding.is_none() {
+                        continue;
+                    }
+                    let segment = map.segment(step.col, step.row);
+                    let (texture_id, local_depth, texture_offset, exit_depth) =
+                        if let Some(hit) = sliding {
+                            // the wall is between two tiles and shows up in one of them
+                            let Some(hit) = hit else {
+                                continue;
+                            };
+                            is_vertical = hit.is_vertical;
+                            let exit_depth = (travelled + hit.exit_depth) * correction;
+                            (hit.texture_id, hit.depth, hit.offset, exit_depth)
+                        } else if segment.is_some() {
+                            // thin walls have no top faces
+                            let Some((hit_depth, offset)) =
+                                map.intersect(step.col, step.row, origin, direction)
+                            else {
+                                continue;
+                            };
+                            let exit_depth = (travelled + hit_depth) * correction;
+                            (texture_id, hit_depth, offset, exit_depth)
+                        } else {
+                            let hit = origin
+                                + Float2d::new(step.depth * direction.x, step.depth * direction.y);
+                            let offset = match (is_vertical, direction.x > 0.0, direction.y > 0.0) {
+                                (true, true, _) => hit.y.rem_euclid(1.0),
+                                (true, false, _) => 1.0 - hit.y.rem_euclid(1.0),
+                                (false, _, true) => 1.0 - hit.x.rem_euclid(1.0),
+                                (false, _, false) => hit.x.rem_euclid(1.0),
+                            };
+                            (texture_id, step.depth, offset, Float::MAX)
+                        };
+                    let hit_depth = travelled + local_depth;
+                    let (floor, top) = map.extent(texture_id);
+                    if floor >= cover.0 && top <= cover.1 {
+                        continue;
+                    }
+                    let rect_depth = hit_depth * correction;
+                    let projected_height = self.screen_distance / (rect_depth + TOL);
+                    let hit =
+                        origin + Float2d::new(local_depth * direction.x, local_depth * direction.y);
+                    let offset =
+                        Float2d::new(CROSSING_OFFSET * direction.x, CROSSING_OFFSET * direction.y);
+                    // the last mirror or portal within the limit is an ordinary wall
+                    if let Some((from, to, turn)) = map
+                        .portal(step.col, step.row)
+                        .filter(|_| portals < self.max_portals)
+                    {
+                        sight_depth.get_or_insert(hit_depth);
+                        view = view.then_portal(from, to, turn);
+                        let index = self.view_index(view);
+                        self.crossing_buffer.push(Crossing {
+                            depth: rect_depth,
+                            view: index,
+                        });
+                        origin = to + rotate(hit + offset - from, turn);
+                        direction = rotate(direction, turn);
+                        travelled = hit_depth;
+                        portals += 1;
+                        continue 'path;
+                    }
+                    if let Some(tint) = map
+                        .mirror(texture_id)
+                        .filter(|_| reflections < self.max_reflections)
+                    {
+                        let normal = match segment {
+                            Some((start, end)) => {
+                                let Float2d { x, y } = end - start;
+                                let length = x.hypot(y);
+                                Float2d::new(-y / length, x / length)
+                            }
+                            None if is_vertical => Float2d::new(1.0, 0.0),
+                            None => Float2d::new(0.0, 1.0),
+                        };
+                        sight_depth.get_or_insert(hit_depth);
+                        view = view.then_mirror(hit, normal, tint);
+                        let index = self.view_index(view);
+                        self.crossing_buffer.push(Crossing {
+                            depth: rect_depth,
+                            view: index,
+                        });
+                        origin = hit - offset;
+                        let dot = 2.0 * direction.dot(&normal);
+                        direction = Float2d::new(
+                            direction.x - dot * normal.x,
+                            direction.y - dot * normal.y,
+                        );
+                        travelled = hit_depth;
+                        reflections += 1;
+                        continue 'path;
+                    }
+                    let is_masked = map.is_masked(texture_id);
+                    self.rect_buffer.push(Rect {
+                        projected_height,
+                        texture_id,
+                        texture_offset,
+                        depth: rect_depth,
+                        exit_depth,
+                        floor,
+                        top,
+                        is_masked,
+                        cap_color: CAP_COLOR,
+                        shade: view.shade,
+                    });
+                    if is_masked || floor > self.eye_height || top < self.eye_height {
+                        continue;
+                    }
+                    sight_depth.get_or_insert(hit_depth);
+                    cover = (cover.0.min(floor), cover.1.max(top));
+                    if cover.0 <= 0.0 && cover.1 >= max_height {
+                        depth = hit_depth;
+                        column_depth = rect_depth;
+                        break 'path;
+                    }
                 }
-                horizontal_x += dx;
-                horizontal_y += dy;
-                horizontal_depth += depth_delta;
             }
-            // verticals
-            let (mut vertical_x, dx) = if cos_a > 0.0 {
-                (tile_x + 1.0, 1.0)
-            } else {
-                (tile_x - TOL, -1.0)
-            };
-            let mut vertical_depth = (vertical_x - pos.x) / cos_a;
-            let mut vertical_y = pos.y + vertical_depth * sin_a;
-            let depth_delta = dx / cos_a;
-            let dy = depth_delta * sin_a;
-            for _ in 0..self.max_depth {
-                let point = Float2d::new(vertical_x, vertical_y);
-                if map.has_collision(point) {
-                    texture_id_vertical = map.texture_id(point);
+            // the player can't see the floor behind walls in front of the eyes
+            let depth = sight_depth.unwrap_or(depth);
+            let (sin_a, cos_a) = ray_angle.sin_cos();
+            self.columns.push(Column {
+                hit: pos + Float2d::new(depth * cos_a, depth * sin_a),
+                depth: column_depth,
+                rects: first..self.rect_buffer.len(),
+                crossings: first_crossing..self.crossing_buffer.len(),
+            });
+            ray_angle += self.delta_angle;
+        }
+    }
+
+    /// Returns index of the view in the list of the seen ones, adds it if it's new
+    fn view_index(&mut self, view: ViewTransform) -> usize {
+        if let Some(index) = self.views.iter().position(|seen| seen.is_close(&view)) {
+            return index;
+        }
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    /// Casts the rays through the sector portals, the walls are drawn with the same rects
+    fn update_sectors(&mut self, sectors: &SectorMap) {
+        let (pos, angle) = (self.position, self.angle);
+        let max_depth = self.max_depth as Float;
+        let start = sectors.sector_at(pos);
+        let mut ray_angle = angle - self.half_fov;
+        for _ in 0..self.rays {
+            let direction = Float2d::new(ray_angle.cos(), ray_angle.sin());
+            // get rid of fishbowl effect
+            let correction = (angle - ray_angle).cos();
+            let first = self.rect_buffer.len();
+            let spans = start.map(|sector| sectors.trace(sector, pos, direction));
+            let mut depth = max_depth;
+            let mut column_depth = Float::MAX;
+            for span in spans.unwrap_or_default() {
+                if span.entry > max_depth {
                     break;
                 }
-                vertical_x += dx;
-                vertical_y += dy;
-                vertical_depth += depth_delta;
-            }
-
-            let (mut depth, texture_id, offset) = if vertical_depth < horizontal_depth {
-                vertical_y %= 1.0;
-                let offset = if cos_a > 0.0 {
-                    vertical_y
-                } else {
-                    1.0 - vertical_y
+                let sector = &sectors.sectors[span.sector];
+                let (entry, exit) = (span.entry * correction, span.exit * correction);
+                for (height, cap_color) in [
+                    (sector.floor, sector.floor_color),
+                    (sector.ceiling, sector.ceiling_color),
+                ] {
+                    self.rect_buffer.push(Rect {
+                        projected_height: self.screen_distance / (entry + TOL),
+                        texture_id: 0,
+                        texture_offset: 0.0,
+                        depth: entry,
+                        exit_depth: exit,
+                        floor: height,
+                        top: height,
+                        is_masked: false,
+                        cap_color,
+                        shade: NO_SHADE,
+                    });
+                }
+                let wall = &sector.walls[span.wall];
+                let (start, _) = sectors.wall(span.sector, span.wall);
+                let Float2d { x, y } =
+                    pos + Float2d::new(direction.x * span.exit, direction.y * span.exit) - start;
+                // textures repeat every map unit along the wall
+                let texture_offset = x.hypot(y).rem_euclid(1.0);
+                let mut push_wall = |floor: Float, top: Float| {
+                    self.rect_buffer.push(Rect {
+                        projected_height: self.screen_distance / (exit + TOL),
+                        texture_id: wall.texture,
+                        texture_offset,
+                        depth: exit,
+                        exit_depth: exit,
+                        floor,
+                        top,
+                        is_masked: false,
+                        cap_color: CAP_COLOR,
+                        shade: NO_SHADE,
+                    })
                 };
-                (vertical_depth, texture_id_vertical, offset)
-            } else {
-                horizontal_x %= 1.0;
-                let offset = if sin_a > 0.0 {
-                    1.0 - horizontal_x
-                } else {
-                    horizontal_x
+                let Some(next) = wall.portal.map(|next| &sectors.sectors[next]) else {
+                    push_wall(sector.floor, sector.ceiling);
+                    depth = span.exit;
+                    column_depth = exit;
+                    break;
                 };
-                (horizontal_depth, texture_id_horizontal, offset)
-            };
-            // get rid of fishbowl effect
-            depth *= (angle - ray_angle).cos();
-
-            let projected_height = self.screen_distance / (depth + TOL);
-            self.rect_buffer.push(Rect {
-                projected_height,
-                texture_id,
-                texture_offset: offset,
-                depth,
+                // steps and lintels between the sectors
+                if next.floor > sector.floor {
+                    push_wall(sector.floor, next.floor.min(sector.ceiling));
+                }
+                if next.ceiling < sector.ceiling {
+                    push_wall(next.ceiling.max(sector.floor), sector.ceiling);
+                }
+            }
+            self.columns.push(Column {
+                hit: pos + Float2d::new(depth * direction.x, depth * direction.y),
+                depth: column_depth,
+                rects: first..self.rect_buffer.len(),
+                crossings: 0..0,
             });
-
             ray_angle += self.delta_angle;
         }
     }
 
+    /// Returns points where the rays hit walls
+    pub fn hits(&self) -> impl Iterator<Item = Float2d> + '_ {
+        self.columns.iter().map(|column| column.hit)
+    }
+
+    /// Screen row of the height in wall units at the depth with the projected wall height
+    fn screen_y(&self, height: Float, projected_height: Float) -> Float {
+        0.5 * self.height + (self.eye_height - height) * projected_height
+    }
+
+    /// Draws walls of every column from far to near
     pub fn draw(&self, commands: &mut Vec<DrawCommand>) {
-        for (ray, rect) in self.rect_buffer.iter().enumerate() {
-            let cmd = DrawCommand::Texture {
-                depth: rect.depth,
-                x: (ray as Float * self.scale) as i32,
-                y: (0.5 * (self.height - rect.projected_height)) as i32,
-                offset: rect.texture_offset,
-                width: self.scale as u32,
-                projected_height: rect.projected_height as u32,
-                texture_id: rect.texture_id,
-            };
-            commands.push(cmd);
+        for ray in 0..self.columns.len() {
+            self.draw_column(commands, ray, Float::MAX);
+        }
+    }
+
+    /// Draws walls of the ray's column nearer than `max_depth` from far to near
+    fn draw_column(&self, commands: &mut Vec<DrawCommand>, ray: usize, max_depth: Float) {
+        let column = &self.columns[ray];
+        // column bounds are rounded separately to avoid gaps for fractional scale
+        let x = (ray as Float * self.scale) as i32;
+        let width = ((ray + 1) as Float * self.scale) as i32 - x;
+        let rects = &self.rect_buffer[column.rects.clone()];
+        let count = rects.partition_point(|rect| rect.depth < max_depth);
+        for rect in rects[..count].iter().rev() {
+            self.draw_cap(commands, rect, x, width as u32);
+            // texture repeats every wall unit from the floor of the tile
+            let mut bottom = rect.floor;
+            while bottom < rect.top {
+                let top = (bottom.floor() + 1.0).min(rect.top);
+                let y = self.screen_y(top, rect.projected_height);
+                let next_y = self.screen_y(bottom, rect.projected_height);
+                commands.push(DrawCommand::Texture {
+                    depth: rect.depth,
+                    x,
+                    y: y as i32,
+                    offset: rect.texture_offset,
+                    src_y: bottom.floor() + 1.0 - top,
+                    src_height: top - bottom,
+                    width: width as u32,
+                    projected_height: (next_y as i32 - y as i32) as u32,
+                    texture_id: rect.texture_id,
+                    shade: rect.shade,
+                });
+                bottom = top;
+            }
         }
     }
+
+    /// Fills the top face of the wall lower than the eye or the bottom face of the higher one
+    fn draw_cap(&self, commands: &mut Vec<DrawCommand>, rect: &Rect, x: i32, width: u32) {
+        let height = if rect.is_masked {
+            return;
+        } else if rect.top < self.eye_height {
+            rect.top
+        } else if rect.floor > self.eye_height {
+            rect.floor
+        } else {
+            return;
+        };
+        let exit_height = self.screen_distance / (rect.exit_depth + TOL);
+        let near_y = self.screen_y(height, rect.projected_height) as i32;
+        let far_y = self.screen_y(height, exit_height) as i32;
+        if near_y == far_y {
+            return;
+        }
+        let (r, g, b) = multiply(rect.cap_color, rect.shade);
+        commands.push(DrawCommand::ColorRGB(r, g, b));
+        // faces starting at the camera are projected far outside of the screen
+        let (y1, y2) = (
+            near_y.min(far_y).max(0),
+            near_y.max(far_y).min(self.height as i32),
+        );
+        if y1 >= y2 {
+            return;
+        }
+        commands.push(DrawCommand::Rectangle {
+            x,
+            y: y1,
+            w: width,
+            h: (y2 - y1) as u32,
+            fill: true,
+        });
+    }
+
+    /// Redraws walls nearer than the sprite in the columns where a masked wall covers it
+    fn draw_masked_over(&self, commands: &mut Vec<DrawCommand>, rays: Range<usize>, depth: Float) {
+        for ray in rays {
+            let has_masked = self.rect_buffer[self.columns[ray].rects.clone()]
+                .iter()
+                .take_while(|rect| rect.depth < depth)
+                .any(|rect| rect.is_masked);
+            if has_masked {
+                self.draw_column(commands, ray, depth);
+            }
+        }
+    }
+
+    /// Screen row hiding the sprite's parts below it in the ray's column,
+    /// opaque walls nearer than the sprite and standing below its `bottom` height cover it,
+    /// sprites seen in mirrors and portals are visible in the columns showing their `view` only
+    fn sprite_clip(&self, ray: usize, depth: Float, bottom: Float, view: Option<usize>) -> Float {
+        let Some(column) = self.columns.get(ray) else {
+            return Float::MIN;
+        };
+        let crossings = &self.crossing_buffer[column.crossings.clone()];
+        let seen = crossings.partition_point(|crossing| crossing.depth < depth);
+        let seen = seen.checked_sub(1).map(|crossing| crossings[crossing].view);
+        if column.depth <= depth || seen != view {
+            return Float::MIN;
+        }
+        self.rect_buffer[column.rects.clone()]
+            .iter()
+            .take_while(|rect| rect.depth < depth)
+            .filter(|rect| !rect.is_masked && rect.top > rect.floor && rect.floor <= bottom)
+            .map(|rect| self.screen_y(rect.top, rect.projected_height))
+            .fold(Float::MAX, Float::min)
+    }
+
+    /// Draws sprites and their images in mirrors and portals from far to near,
+    /// columns behind walls are skipped
+    pub fn draw_sprites(
+        &self,
+        commands: &mut Vec<DrawCommand>,
+        sprites: impl Iterator<Item = (Float2d, Sprite)>,
+    ) {
+        let mut visible = Vec::new();
+        for (position, sprite) in sprites {
+            let images = self
+                .views
+                .iter()
+                .enumerate()
+                .map(|(index, view)| (view.apply(position), Some(index)));
+            for (position, view) in std::iter::once((position, None)).chain(images) {
+                let Float2d { x, y } = position - self.position;
+                // angle between the view direction and the sprite in range -PI..PI
+                let theta = (y.atan2(x) - self.angle + PI).rem_euclid(2.0 * PI) - PI;
+                let depth = x.hypot(y) * theta.cos();
+                if depth > MIN_SPRITE_DEPTH {
+                    visible.push((depth, theta, sprite, view));
+                }
+            }
+        }
+        visible.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (depth, theta, sprite, view) in visible {
+            let wall_height = self.screen_distance / depth;
+            let size = wall_height * sprite.height;
+            let left = (theta + self.half_fov) / self.delta_angle * self.scale - 0.5 * size;
+            let top = self.screen_y(sprite.elevation, wall_height) - size;
+            let first_ray = (left / self.scale).floor().max(0.0) as usize;
+            let last_ray = (((left + size) / self.scale).ceil().max(0.0) as usize).min(self.rays);
+            let shade = view.map_or(NO_SHADE, |index| self.views[index].shade);
+            // emit runs of columns with the same visible part
+            let mut start: Option<(usize, i32)> = None;
+            for ray in first_ray..=last_ray {
+                let clip = if ray < last_ray {
+                    Some(
+                        self.sprite_clip(ray, depth, sprite.elevation, view)
+                            .min(top + size) as i32,
+                    )
+                    .filter(|clip| *clip > top as i32)
+                } else {
+                    None
+                };
+                match (clip, start) {
+                    (Some(clip), Some((_, run_clip))) if clip == run_clip => {}
+                    (_, Some((first, run_clip))) => {
+                        start = clip.map(|clip| (ray, clip));
+                        let x1 = (first as Float * self.scale).max(left);
+                        let x2 = (ray as Float * self.scale).min(left + size);
+                        if x2 <= x1 {
+                            continue;
+                        }
+                        let is_clipped = run_clip < (top + size) as i32;
+                        if is_clipped {
+                            commands.push(DrawCommand::ClipRect {
+                                x: x1 as i32,
+                                y: top as i32,
+                                w: (x2 - x1).ceil() as u32,
+                                h: (run_clip - top as i32) as u32,
+                            });
+                        }
+                        // redrawn walls may change the color
+                        let (r, g, b) = multiply(sprite.color, shade);
+                        commands.push(DrawCommand::ColorRGB(r, g, b));
+                        commands.push(DrawCommand::Sprite {
+                            texture_id: sprite.texture_id,
+                            x: x1 as i32,
+                            y: top as i32,
+                            width: (x2 - x1).ceil() as u32,
+                            height: size as u32,
+                            src_x: (x1 - left) / size,
+                            src_width: (x2 - x1) / size,
+                            shade,
+                        });
+                        if is_clipped {
+                            commands.push(DrawCommand::ResetClip);
+                        }
+                        self.draw_masked_over(commands, first..ray, depth);
+                    }
+                    (Some(clip), None) => start = Some((ray, clip)),
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+}
+
+/// Multiplies the colors' components as fractions of 255
+fn multiply(color: (u8, u8, u8), shade: (u8, u8, u8)) -> (u8, u8, u8) {
+    let channel = |a: u8, b: u8| (a as u16 * b as u16 / 255) as u8;
+    (
+        channel(color.0, shade.0),
+        channel(color.1, shade.1),
+        channel(color.2, shade.2),
+    )
 }
@@ -0,0 +1,51 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Polls modification time of the watched files
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn with_files<P: AsRef<Path>>(paths: &[P]) -> Self {
+        let files = paths
+            .iter()
+            .map(|path| {
+                let path = path.as_ref().to_path_buf();
+                let time = Self::modification_time(&path);
+                (path, time)
+            })
+            .collect();
+        Self {
+            files,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Returns true if any of the watched files was changed since the last call
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let mut is_changed = false;
+        for (path, time) in self.files.iter_mut() {
+            let current = Self::modification_time(path);
+            if current != *time {
+                *time = current;
+                is_changed = true;
+            }
+        }
+        is_changed
+    }
+
+    fn modification_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+}
@@ -1,76 +1,144 @@
 use std::{
     collections::HashMap,
+    path::Path,
     time::{Duration, Instant},
 };
 
 use sdl2::{
-    event::Event,
+    event::{Event, WindowEvent},
     image::LoadTexture,
     keyboard::Keycode,
-    pixels::Color,
+    pixels::{Color, PixelFormatEnum},
     rect::{Point, Rect},
     render::{Texture, TextureCreator, WindowCanvas},
-    video::WindowContext,
+    surface::Surface,
+    video::{FullscreenType, WindowContext},
     EventPump,
 };
 
-use crate::{common::DrawCommand, control::ControlEvent};
+use crate::{
+    common::DrawCommand,
+    control::ControlEvent,
+    hud::{GLYPH_COLUMNS, GLYPH_SIZE, TEXTURE_ID_FONT},
+    reload::FileWatcher,
+    settings::FullscreenMode,
+};
 use crate::{common::Float, scene::Scene};
 
+const TEXTURE_ASSETS: [(i32, &str); 19] = [
+    (1, "assets/textures/1.png"),
+    (2, "assets/textures/2.png"),
+    (3, "assets/textures/3.png"),
+    (4, "assets/textures/4.png"),
+    (5, "assets/textures/5.png"),
+    // grate with transparent holes for masked tiles
+    (6, "assets/textures/6.png"),
+    // thin and diagonal walls of the sample episode reuse the wall textures
+    (7, "assets/textures/2.png"),
+    (8, "assets/textures/3.png"),
+    // mirror frame shown when the reflections run out
+    (9, "assets/textures/5.png"),
+    (100, "assets/sprites/enemy.png"),
+    (101, "assets/sprites/item.png"),
+    (102, "assets/sprites/decoration.png"),
+    (103, "assets/sprites/projectile.png"),
+    (104, "assets/sprites/spark.png"),
+    // the player's body seen in mirrors reuses the enemy sprite
+    (105, "assets/sprites/enemy.png"),
+    (110, "assets/sprites/pistol.png"),
+    (111, "assets/sprites/launcher.png"),
+    (998, "assets/font.png"),
+    (999, "assets/textures/sky.png"),
+];
+
 pub struct RendererSDL<'a> {
     canvas: WindowCanvas,
     event_pump: EventPump,
     scene: &'a mut Scene,
+    is_fullscreen: bool,
 }
 
 impl<'a> RendererSDL<'a> {
     pub fn new(scene: &'a mut Scene) -> Result<Self, String> {
+        Self::with_window(scene, false)
+    }
+
+    /// Creates renderer with a hidden window and without vsync for non-interactive modes
+    pub fn headless(scene: &'a mut Scene) -> Result<Self, String> {
+        Self::with_window(scene, true)
+    }
+
+    fn with_window(scene: &'a mut Scene, is_headless: bool) -> Result<Self, String> {
         let context = sdl2::init()?;
         let video_subsystem = context.video()?;
         let window_size = scene.window_size();
-        let window = video_subsystem
-            .window("FPS: ??", window_size.width, window_size.height)
-            .position_centered()
-            .build()
-            .map_err(|op| op.to_string())?;
+        let mut window_builder =
+            video_subsystem.window("Raycaster", window_size.width, window_size.height);
+        window_builder.position_centered();
+        if is_headless {
+            window_builder.hidden();
+        } else if scene.is_resizable() {
+            window_builder.resizable();
+        }
+        let window = window_builder.build().map_err(|op| op.to_string())?;
         // context.mouse().show_cursor(false);
-        let canvas = window
-            .into_canvas()
-            .accelerated()
-            .target_texture()
-            .present_vsync()
-            .build()
-            .map_err(|op| op.to_string())?;
+        let mut canvas_builder = window.into_canvas().accelerated().target_texture();
+        if !is_headless {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let canvas = canvas_builder.build().map_err(|op| op.to_string())?;
         let event_pump = context.event_pump()?;
-        Ok(Self {
+        let mut renderer = Self {
             canvas,
             event_pump,
             scene,
-        })
+            is_fullscreen: false,
+        };
+        if !is_headless {
+            renderer.update_fullscreen()?;
+        }
+        Ok(renderer)
+    }
+
+    /// Switches window to the fullscreen mode requested by the scene
+    fn update_fullscreen(&mut self) -> Result<(), String> {
+        let is_fullscreen = self.scene.is_fullscreen();
+        if is_fullscreen == self.is_fullscreen {
+            return Ok(());
+        }
+        let fullscreen_type = match (is_fullscreen, self.scene.fullscreen_mode()) {
+            (false, _) => FullscreenType::Off,
+            (true, FullscreenMode::Borderless) => FullscreenType::Desktop,
+            (true, FullscreenMode::Exclusive) => FullscreenType::True,
+        };
+        self.canvas.window_mut().set_fullscreen(fullscreen_type)?;
+        self.is_fullscreen = is_fullscreen;
+        Ok(())
     }
 
     pub fn run(&mut self) -> Result<(), String> {
-        self.scene.prepare();
+        self.scene.prepare()?;
+        self.scene.show_main_menu();
         let texture_creator = self.canvas.texture_creator();
-        let textures = Self::load_textures(&texture_creator)?;
-        let mut frames = 0;
-        let mut time = Instant::now();
+        let mut textures = HashMap::new();
+        self.load_textures(&texture_creator, &mut textures);
+        let mut texture_watcher = FileWatcher::with_files(&TEXTURE_ASSETS.map(|(_, path)| path));
         let mut draw_commands = Vec::with_capacity(1000);
         let target_duration = (1000 / self.scene.target_fps()) as u128;
         while self.scene.is_running() {
             let frame_start = Instant::now();
             draw_commands.clear();
             self.process_events();
+            self.update_fullscreen()?;
             self.scene.update();
-            self.draw(&textures, &mut draw_commands)?;
-            frames += 1;
-            let elapsed = time.elapsed();
-            if elapsed.as_millis() > 1000 {
-                time = Instant::now();
-                let title = format!("FPS: {frames}");
-                _ = self.canvas.window_mut().set_title(&title);
-                frames = 0;
+            if self.scene.take_textures_outdated() || texture_watcher.poll() {
+                self.load_textures(&texture_creator, &mut textures);
             }
+            self.draw(&mut textures, &mut draw_commands)?;
+            // measure before present to exclude vsync waiting
+            self.scene
+                .on_frame_rendered(frame_start.elapsed().as_secs_f32());
+            self.canvas.present();
             let suspend_ms = target_duration.saturating_sub(frame_start.elapsed().as_millis());
             if suspend_ms > 0 {
                 let duration = Duration::from_millis(suspend_ms as u64);
@@ -80,9 +148,48 @@ impl<'a> RendererSDL<'a> {
         Ok(())
     }
 
+    /// Renders a single frame and saves it as BMP image
+    pub fn screenshot<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        self.scene.prepare()?;
+        let texture_creator = self.canvas.texture_creator();
+        let mut textures = HashMap::new();
+        self.load_textures(&texture_creator, &mut textures);
+        let mut draw_commands = Vec::new();
+        self.scene.update();
+        self.draw(&mut textures, &mut draw_commands)?;
+        let (width, height) = self.canvas.output_size()?;
+        let format = PixelFormatEnum::RGB24;
+        let mut pixels = self.canvas.read_pixels(None, format)?;
+        let surface = Surface::from_data(&mut pixels, width, height, width * 3, format)?;
+        surface.save_bmp(path)
+    }
+
+    /// Renders `frames` frames while the player turns around and returns frame times in seconds
+    pub fn bench(&mut self, frames: usize) -> Result<Vec<f32>, String> {
+        self.scene.prepare()?;
+        let texture_creator = self.canvas.texture_creator();
+        let mut textures = HashMap::new();
+        self.load_textures(&texture_creator, &mut textures);
+        let mut draw_commands = Vec::with_capacity(1000);
+        let mut frame_times = Vec::with_capacity(frames);
+        self.scene.process_events(&[ControlEvent::Keyboard {
+            key_code: Keycode::Right as i32,
+            is_pressed: true,
+        }]);
+        for _ in 0..frames {
+            let frame_start = Instant::now();
+            draw_commands.clear();
+            self.scene.update();
+            self.draw(&mut textures, &mut draw_commands)?;
+            self.canvas.present();
+            frame_times.push(frame_start.elapsed().as_secs_f32());
+        }
+        Ok(frame_times)
+    }
+
     fn draw(
         &mut self,
-        textures: &HashMap<i32, Texture>,
+        textures: &mut HashMap<i32, Texture>,
         commands: &mut Vec<DrawCommand>,
     ) -> Result<(), String> {
         self.scene.draw(commands);
@@ -107,17 +214,44 @@ impl<'a> RendererSDL<'a> {
                     let end = Point::new(x2, y2);
                     self.canvas.draw_line(start, end)?;
                 }
+                DrawCommand::ClipRect { x, y, w, h } => {
+                    self.canvas.set_clip_rect(Rect::new(x, y, w, h));
+                }
+                DrawCommand::ResetClip => {
+                    self.canvas.set_clip_rect(None);
+                }
+                DrawCommand::Glyph { code, x, y, size } => {
+                    let Some(texture) = textures.get_mut(&TEXTURE_ID_FONT) else {
+                        continue;
+                    };
+                    let Some(index) = (code as u32).checked_sub(b' ' as u32) else {
+                        continue;
+                    };
+                    let src = Rect::new(
+                        ((index % GLYPH_COLUMNS) * GLYPH_SIZE) as i32,
+                        ((index / GLYPH_COLUMNS) * GLYPH_SIZE) as i32,
+                        GLYPH_SIZE,
+                        GLYPH_SIZE,
+                    );
+                    let color = self.canvas.draw_color();
+                    texture.set_color_mod(color.r, color.g, color.b);
+                    self.canvas
+                        .copy(texture, src, Rect::new(x, y, size, size))?;
+                }
                 DrawCommand::Texture {
                     depth,
                     x,
                     y,
                     offset,
+                    src_y,
+                    src_height,
                     width,
                     projected_height,
                     texture_id,
+                    shade: (r, g, b),
                 } => {
                     let dst = Rect::new(x, y, width, projected_height);
-                    let Some(texture) = textures.get(&texture_id) else {
+                    let Some(texture) = textures.get_mut(&texture_id) else {
                         // draw gray-scale bars in case of missing texture
                         let clr = (255.0 / (1.0 + depth.powi(5) * 0.00002)) as u8;
                         self.canvas.set_draw_color(Color::RGB(clr, clr, clr));
@@ -126,8 +260,34 @@ impl<'a> RendererSDL<'a> {
                     };
                     let query = texture.query();
                     let (w, h) = (query.width, query.height);
-                    let src =
-                        Rect::new((offset * (w as Float - width as Float)) as i32, 0, width, h);
+                    let src = Rect::new(
+                        (offset * (w as Float - width as Float)) as i32,
+                        (src_y * h as Float) as i32,
+                        width,
+                        ((src_height * h as Float).ceil() as u32).max(1),
+                    );
+                    texture.set_color_mod(r, g, b);
+                    self.canvas.copy(texture, src, dst)?;
+                }
+                DrawCommand::Sprite {
+                    texture_id,
+                    x,
+                    y,
+                    width,
+                    height,
+                    src_x,
+                    src_width,
+                    shade: (r, g, b),
+                } => {
+                    let dst = Rect::new(x, y, width, height);
+                    let Some(texture) = textures.get_mut(&texture_id) else {
+                        self.canvas.fill_rect(dst)?;
+                        continue;
+                    };
+                    let query = texture.query();
+                    let (w, h) = (query.width as Float, query.height);
+                    let src = Rect::new((src_x * w) as i32, 0, (src_width * w).ceil() as u32, h);
+                    texture.set_color_mod(r, g, b);
                     self.canvas.copy(texture, src, dst)?;
                 }
                 DrawCommand::SkyTexture { id, offset } => {
@@ -159,7 +319,6 @@ impl<'a> RendererSDL<'a> {
                 }
             }
         }
-        self.canvas.present();
         Ok(())
     }
 
@@ -167,11 +326,7 @@ impl<'a> RendererSDL<'a> {
         let mut events = Vec::<ControlEvent>::new();
         for event in self.event_pump.poll_iter() {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => self.scene.on_terminate(),
+                Event::Quit { .. } => self.scene.on_terminate(),
                 Event::KeyDown {
                     keycode: Some(keycode),
                     ..
@@ -190,15 +345,20 @@ impl<'a> RendererSDL<'a> {
                         is_pressed: false,
                     });
                 }
-                Event::MouseMotion {
-                    x, y, xrel, yrel, ..
+                Event::MouseMotion { xrel, .. } => {
+                    events.push(ControlEvent::MouseMotion { x_rel: xrel });
+                }
+                Event::TextInput { text, .. } => {
+                    events.push(ControlEvent::TextInput { text });
+                }
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
                 } => {
-                    events.push(ControlEvent::MouseMotion {
-                        x,
-                        y,
-                        x_rel: xrel,
-                        y_rel: yrel,
-                    });
+                    // output size may differ from window size on high-dpi displays
+                    if let Ok((width, height)) = self.canvas.output_size() {
+                        events.push(ControlEvent::WindowResized { width, height });
+                    }
                 }
                 _ => {}
             }
@@ -206,25 +366,24 @@ impl<'a> RendererSDL<'a> {
         self.scene.process_events(&events);
     }
 
-    fn load_textures(
-        texture_creator: &TextureCreator<WindowContext>,
-    ) -> Result<HashMap<i32, Texture>, String> {
-        let assets = [
-            (1, "assets/textures/1.png"),
-            (2, "assets/textures/2.png"),
-            (3, "assets/textures/3.png"),
-            (4, "assets/textures/4.png"),
-            (5, "assets/textures/5.png"),
-            (999, "assets/textures/sky.png"),
-        ];
-        let mut textures = HashMap::new();
-        for (id, path) in assets {
-            let Ok(texture) = texture_creator.load_texture(path) else {
-                println!("[ERR] failed to load texture with id: {id} at '{path}'");
-                continue;
-            };
-            textures.insert(id, texture);
+    /// Loads the textures into `textures`, a texture which fails to load keeps its previous
+    /// version. Errors are logged and shown in the console
+    fn load_textures<'t>(
+        &mut self,
+        texture_creator: &'t TextureCreator<WindowContext>,
+        textures: &mut HashMap<i32, Texture<'t>>,
+    ) {
+        for (id, path) in TEXTURE_ASSETS {
+            match texture_creator.load_texture(path) {
+                Ok(texture) => {
+                    textures.insert(id, texture);
+                }
+                Err(err) => {
+                    let err = format!("failed to load texture with id: {id} at '{path}': {err}");
+                    println!("[ERR] {err}");
+                    self.scene.report_error(&err);
+                }
+            }
         }
-        Ok(textures)
     }
 }
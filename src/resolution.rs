@@ -0,0 +1,72 @@
+use crate::{
+    common::Float,
+    settings::{Quality, SceneSettings},
+};
+
+// frame time smoothing factor
+const SMOOTHING: Float = 0.1;
+// quality goes down when smoothed frame time exceeds this part of the budget
+const OVER_BUDGET: Float = 0.95;
+// ...and goes up when it stays below this part
+const UNDER_BUDGET: Float = 0.6;
+// minimal time (in seconds) between two quality changes
+const COOLDOWN: Float = 1.0;
+
+/// Picks the render quality, lowers it when frame time goes over budget
+/// and restores it back when there is enough headroom
+pub struct ResolutionScaler {
+    max_quality: Quality,
+    quality: Quality,
+    is_dynamic: bool,
+    frame_budget: Float,
+    frame_time: Float,
+    cooldown: Float,
+}
+
+impl ResolutionScaler {
+    pub fn new(opts: &SceneSettings) -> Self {
+        let frame_budget = 1.0 / opts.fps as Float;
+        Self {
+            max_quality: opts.quality,
+            quality: opts.quality,
+            is_dynamic: opts.dynamic_resolution,
+            frame_budget,
+            frame_time: frame_budget * UNDER_BUDGET,
+            cooldown: COOLDOWN,
+        }
+    }
+
+    /// Accepts the time spent on a frame (without vsync/sleep) in seconds
+    /// Returns true if quality level was changed
+    pub fn update(&mut self, frame_time: Float) -> bool {
+        if !self.is_dynamic {
+            return false;
+        }
+        self.frame_time += SMOOTHING * (frame_time - self.frame_time);
+        self.cooldown -= frame_time.max(self.frame_budget);
+        if self.cooldown > 0.0 {
+            return false;
+        }
+        let next = if self.frame_time > self.frame_budget * OVER_BUDGET {
+            self.quality.lower()
+        } else if self.frame_time < self.frame_budget * UNDER_BUDGET {
+            self.quality.higher().filter(|q| *q <= self.max_quality)
+        } else {
+            None
+        };
+        let Some(quality) = next else {
+            return false;
+        };
+        self.quality = quality;
+        self.cooldown = COOLDOWN;
+        true
+    }
+
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    pub fn ray_divisor(&self) -> usize {
+        self.quality.ray_divisor()
+    }
+}
@@ -0,0 +1,159 @@
+use std::{fs, path::Path};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    automap::ExploredCells, entity::EntitySnapshot, episode::LevelStats, inventory::Inventory,
+    weapon::Weapons,
+};
+
+/// Increased on every incompatible change of the save format
+pub const SAVE_VERSION: u32 = 3;
+pub const QUICKSAVE_PATH: &str = "saves/quicksave.toml";
+
+#[derive(Deserialize, Serialize)]
+pub struct PlayerState {
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    pub health: i32,
+    pub armor: i32,
+    pub inventory: Inventory,
+}
+
+/// Snapshot of the running level.
+/// Tables must follow plain values to be serializable to TOML
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SaveGame {
+    pub version: u32,
+    pub map: String,
+    /// index of the episode's level, the level is taken from the episode instead of settings
+    pub episode_level: Option<usize>,
+    pub level_time: f32,
+    /// (column, row, value) of the tiles which differ from the map file,
+    /// e.g. opened doors and tiles changed by triggers
+    pub changed_tiles: Vec<(usize, usize, i32)>,
+    /// indices of the level triggers which won't fire anymore
+    pub fired_triggers: Vec<usize>,
+    pub player: PlayerState,
+    pub weapons: Weapons,
+    pub stats: LevelStats,
+    pub explored: ExploredCells,
+    pub entities: Vec<EntitySnapshot>,
+}
+
+impl SaveGame {
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let content = toml::to_string(self).map_err(|err| err.to_string())?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+        }
+        fs::write(path, content).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// Reads the save, saves of other versions are rejected
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content =
+            fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let value = content
+            .parse::<toml::Value>()
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        let version = value
+            .get("version")
+            .and_then(|version| version.as_integer())
+            .ok_or_else(|| format!("{}: save version is missing", path.display()))?;
+        if version != SAVE_VERSION as i64 {
+            return Err(format!(
+                "{}: save version {version} isn't supported, expected {SAVE_VERSION}",
+                path.display()
+            ));
+        }
+        let save = value
+            .try_into::<Self>()
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        save.weapons
+            .validate()
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(save)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{entity::Entities, inventory::KeyColor, settings::EntitySpawn, weapon::WeaponKind};
+
+    fn save_game() -> SaveGame {
+        let spawns = [
+            "kind = \"enemy\"\nx = 2.5\ny = 3.5\nname = \"guard\"",
+            "kind = \"item\"\nx = 4.5\ny = 1.5\nitem = \"ammo\"\namount = 7",
+        ]
+        .map(|spawn| toml::from_str::<EntitySpawn>(spawn).unwrap());
+        let mut inventory = Inventory::default();
+        inventory.add_key(KeyColor::Blue);
+        inventory.add_score(250);
+        let mut weapons = Weapons::default();
+        weapons.add_ammo(WeaponKind::Pistol, 5);
+        let mut stats = LevelStats::new(3, 2, 1);
+        stats.kills = 1;
+        stats.secrets.push(0);
+        SaveGame {
+            version: SAVE_VERSION,
+            map: "assets/map.pbm".to_string(),
+            episode_level: Some(1),
+            level_time: 12.5,
+            changed_tiles: vec![(3, 4, 0), (5, 6, 2)],
+            fired_triggers: vec![1],
+            player: PlayerState {
+                x: 1.5,
+                y: 2.5,
+                angle: 0.75,
+                health: 80,
+                armor: 20,
+                inventory,
+            },
+            weapons,
+            stats,
+            explored: ExploredCells::new(8, 6),
+            entities: Entities::with_spawns(&spawns).snapshot(),
+        }
+    }
+
+    /// Writes the save to a file for the test, reads it back and removes the file
+    fn write_and_read(name: &str, save: &SaveGame) -> Result<SaveGame, String> {
+        let path = env::temp_dir().join(format!("raycaster-save-{name}.toml"));
+        save.write(&path)?;
+        let read = SaveGame::read(&path);
+        fs::remove_file(&path).unwrap();
+        read
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let save = save_game();
+        let read = write_and_read("round-trip", &save).unwrap();
+        assert_eq!(
+            toml::to_string(&read).unwrap(),
+            toml::to_string(&save).unwrap()
+        );
+        assert_eq!(read.changed_tiles, save.changed_tiles);
+        assert_eq!(read.player.inventory.keys(), [KeyColor::Blue]);
+        assert_eq!(read.player.inventory.score(), 250);
+        assert_eq!(read.entities.len(), 2);
+        assert_eq!(read.entities[0].spawn.name.as_deref(), Some("guard"));
+        assert_eq!(read.entities[1].spawn.amount, Some(7));
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut save = save_game();
+        save.version = SAVE_VERSION - 1;
+        let err = write_and_read("old-version", &save).err().unwrap();
+        assert!(err.contains("isn't supported"), "{err}");
+        save.version = SAVE_VERSION + 1;
+        assert!(write_and_read("new-version", &save).is_err());
+    }
+}
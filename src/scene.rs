@@ -1,65 +1,638 @@
-use std::time::Instant;
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use crate::{
+    automap::{Automap, ExploredCells, MapMarker},
     background::Background,
-    common::{DrawCommand, Float2d, Size2d},
+    common::{draw_border, DrawCommand, Float, Float2d, ScreenSize},
+    console::{Console, ConsoleAction},
     control::{ControlEvent, ControllerState},
+    entity::{Entities, EntityEvent, Pickup, HIT_OFFSET},
+    episode::{Episode, LevelStats},
+    hud::{Hud, HudStatus},
+    inventory::ItemKind,
+    menu::{find_levels, Menu, MenuAction, MenuContext, Screen},
+    minimap::Minimap,
     player::Player,
+    ray,
     raycaster::RayCaster,
-    settings::Settings,
-    walls::Walls,
+    reload::FileWatcher,
+    resolution::ResolutionScaler,
+    save::{PlayerState, SaveGame, QUICKSAVE_PATH, SAVE_VERSION},
+    settings::{parse_angle, FullscreenMode, LevelInfo, Settings, SettingsSource},
+    triggers::{TriggerAction, TriggerContext, Triggers},
+    walls::{overlaps_tile, Walls},
+    weapon::{Shot, WeaponKind, Weapons},
 };
 
+// seconds between death and the death screen
+const DEATH_SCREEN_DELAY: Float = 1.5;
+// max distance to a door the player can open
+const USE_RANGE: Float = 1.5;
+// pixels, the frame is shown while the settings have errors
+const ERROR_FRAME_THICKNESS: u32 = 4;
+
+const COMMANDS: [&str; 14] = [
+    "help", "clear", "tp", "fov", "noclip", "reload", "player", "set", "get", "cvars", "exit",
+    "entities", "save", "load",
+];
+
+/// Player's stats carried between the levels of an episode, keys stay on their level
+#[derive(Clone)]
+struct CarriedStats {
+    health: i32,
+    armor: i32,
+    score: u32,
+    weapons: Weapons,
+}
+
+impl CarriedStats {
+    fn new(player: &Player, weapons: &Weapons) -> Self {
+        Self {
+            health: player.health(),
+            armor: player.armor(),
+            score: player.inventory().score(),
+            weapons: weapons.clone(),
+        }
+    }
+
+    fn apply(&self, player: &mut Player, weapons: &mut Weapons) {
+        player.reset_stats(self.health, self.armor);
+        player.inventory_mut().add_score(self.score);
+        *weapons = self.weapons.clone();
+    }
+}
+
+#[derive(Default)]
 pub enum State {
+    #[default]
     Initial,
     Running,
     Terminated,
 }
 
-impl Default for State {
-    fn default() -> Self {
-        Self::Initial
-    }
-}
-
 pub struct Scene {
     settings: Settings,
+    settings_source: SettingsSource,
+    screen_size: ScreenSize,
     walls: Walls,
     state: State,
+    menu: Menu,
+    /// a level was started from the menu
+    has_session: bool,
+    level_time: Float,
+    entities: Entities,
+    weapons: Weapons,
+    triggers: Triggers,
+    episode: Option<Episode>,
+    /// index of the episode's level being played
+    episode_level: Option<usize>,
+    stats: LevelStats,
+    /// player's stats on the level start, restored on restart
+    carried: Option<CarriedStats>,
     // -- drawables
     player: Player,
     ray_caster: RayCaster,
     background: Background,
+    minimap: Minimap,
+    automap: Automap,
+    explored: ExploredCells,
+    hud: Hud,
+    console: Console,
     // --
+    resolution_scaler: ResolutionScaler,
     controller_state: ControllerState,
     time: Instant,
+    // -- hot reload
+    file_watcher: Option<FileWatcher>,
+    textures_outdated: bool,
+    error: Option<String>,
 }
 
 impl Scene {
-    pub fn new(settings: Settings) -> Self {
+    pub fn new(settings: Settings, settings_source: SettingsSource) -> Self {
         let opts = &settings.scene;
-        let ray_caster = RayCaster::new(opts);
-        let player = Player::new(&settings.player, opts.tile_size);
-        let walls = Walls::new(opts.tile_size);
-        let background = Background::new(opts.screen_size());
+        let resolution_scaler = ResolutionScaler::new(opts);
+        let ray_caster = RayCaster::new(opts, resolution_scaler.ray_divisor());
+        let player = Player::new(&settings.player);
+        let walls = Walls::default();
+        let screen_size = opts.screen_size();
+        let background = Background::new(screen_size);
+        let minimap = Minimap::new(settings.minimap, opts.tile_size, screen_size);
+        let hud = Hud::new(settings.hud, screen_size);
+        let controller_state = ControllerState {
+            fullscreen: opts.fullscreen,
+            ..ControllerState::default()
+        };
         Self {
             settings,
+            settings_source,
+            screen_size,
             walls,
             state: State::default(),
+            menu: Menu::default(),
+            has_session: false,
+            level_time: 0.0,
+            entities: Entities::default(),
+            weapons: Weapons::default(),
+            triggers: Triggers::default(),
+            episode: None,
+            episode_level: None,
+            stats: LevelStats::default(),
+            carried: None,
             player,
             ray_caster,
             background,
-            controller_state: ControllerState::default(),
+            minimap,
+            automap: Automap::default(),
+            explored: ExploredCells::default(),
+            hud,
+            console: Console::default(),
+            resolution_scaler,
+            controller_state,
             time: Instant::now(),
+            file_watcher: None,
+            textures_outdated: false,
+            error: None,
         }
     }
 
-    pub fn prepare(&mut self) {
+    pub fn prepare(&mut self) -> Result<(), String> {
+        self.episode = self
+            .settings
+            .campaign
+            .episode
+            .as_ref()
+            .map(Episode::load)
+            .transpose()?;
         let level_info = &self.settings.level;
-        self.walls.prepare(&level_info.map);
-        self.player
-            .setup(Float2d::new(level_info.player_x, level_info.player_y), 0.0);
+        self.walls.prepare(level_info)?;
+        let (cols, rows) = self.walls.size();
+        self.explored = ExploredCells::new(cols, rows);
+        self.entities = Entities::with_spawns(&level_info.entities);
+        self.triggers = Triggers::new(level_info.triggers.len());
+        self.player.setup(
+            Float2d::new(level_info.player_x, level_info.player_y),
+            level_info.player_angle,
+        );
+        self.player.reset_stats(level_info.health, level_info.armor);
+        self.setup_file_watcher();
         self.state = State::Running;
+        Ok(())
+    }
+
+    pub fn show_main_menu(&mut self) {
+        self.menu.reset(Screen::MainMenu);
+        self.controller_state.release_keys();
+    }
+
+    /// Restarts the current level or loads `map` and starts it.
+    /// `carried` stats replace the level's initial ones
+    fn start_level(&mut self, map: Option<String>, carried: Option<CarriedStats>) {
+        if let Err(err) = self.load_level(map) {
+            println!("[ERR] failed to load level: {err}");
+            self.hud
+                .show_message(format!("Failed to load level: {err}"));
+            return;
+        }
+        let (cols, rows) = self.walls.size();
+        self.explored = ExploredCells::new(cols, rows);
+        let level_info = &self.settings.level;
+        self.entities = Entities::with_spawns(&level_info.entities);
+        self.triggers = Triggers::new(level_info.triggers.len());
+        let mut position = Float2d::new(level_info.player_x, level_info.player_y);
+        if !self.walls.is_walkable(position) {
+            // spawn point may be set for another map
+            position = self.walls.first_walkable().unwrap_or(position);
+        }
+        self.player.setup(position, level_info.player_angle);
+        self.player.reset_stats(level_info.health, level_info.armor);
+        self.weapons = Weapons::default();
+        if let Some(carried) = &carried {
+            carried.apply(&mut self.player, &mut self.weapons);
+        }
+        self.carried = carried;
+        self.stats = LevelStats::new(
+            self.entities.enemies_left(),
+            self.entities.items_left(),
+            level_info.secrets.len() + level_info.push_walls.len(),
+        );
+        self.level_time = 0.0;
+        self.has_session = true;
+        self.menu.clear();
+        self.controller_state.release_keys();
+    }
+
+    /// Makes the episode's level current, it still needs to be started
+    /// Returns the level of the current episode by index
+    fn episode_level_info(&self, index: usize) -> Result<LevelInfo, String> {
+        self.episode
+            .as_ref()
+            .ok_or_else(|| "no episode is configured".to_string())?
+            .levels
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("episode has no level #{}", index + 1))
+    }
+
+    fn select_episode_level(&mut self, index: usize) -> Result<(), String> {
+        let level = self.episode_level_info(index)?;
+        let mut walls = Walls::default();
+        walls.prepare(&level)?;
+        let mut settings = std::mem::take(&mut self.settings);
+        settings.level = level;
+        self.apply_settings(settings, Some(walls));
+        self.episode_level = Some(index);
+        Ok(())
+    }
+
+    /// Starts the episode's level, `carried` stats come from the previous level
+    fn start_episode_level(&mut self, index: usize, carried: Option<CarriedStats>) {
+        match self.select_episode_level(index) {
+            Ok(_) => self.start_level(None, carried),
+            Err(err) => {
+                println!("[ERR] failed to load level: {err}");
+                self.hud
+                    .show_message(format!("Failed to load level: {err}"));
+            }
+        }
+    }
+
+    fn start_next_level(&mut self) {
+        let Some(index) = self.episode_level else {
+            return;
+        };
+        let carried = CarriedStats::new(&self.player, &self.weapons);
+        self.start_episode_level(index + 1, Some(carried));
+    }
+
+    /// Switches to `map` if provided, the map is re-read anyway to close opened doors
+    fn load_level(&mut self, map: Option<String>) -> Result<(), String> {
+        if let Some(map) = map {
+            let value = toml::Value::String(map).to_string();
+            self.set_value("level.map", &value)?;
+        }
+        self.walls.prepare(&self.settings.level)
+    }
+
+    /// Returns the path of a named save, the quicksave if the name is missing.
+    /// Saves stay next to the quicksave, names with path separators are rejected
+    fn save_path(name: Option<&str>) -> Result<PathBuf, String> {
+        let Some(name) = name else {
+            return Ok(PathBuf::from(QUICKSAVE_PATH));
+        };
+        let is_plain = !name.is_empty()
+            && !name.contains(['/', '\\'])
+            && !name.contains("..")
+            && Path::new(name).is_relative();
+        if !is_plain {
+            return Err(format!("invalid save name '{name}'"));
+        }
+        Ok(Path::new(QUICKSAVE_PATH).with_file_name(format!("{name}.toml")))
+    }
+
+    fn save_game(&self, path: &Path) -> Result<(), String> {
+        if !self.has_session || self.player.is_dead() {
+            return Err("no game to save".to_string());
+        }
+        let mut original = Walls::default();
+        original.prepare(&self.settings.level)?;
+        let position = self.player.pos();
+        let save = SaveGame {
+            version: SAVE_VERSION,
+            map: self.settings.level.map.clone(),
+            episode_level: self.episode_level,
+            level_time: self.level_time,
+            changed_tiles: self.walls.changed_tiles(&original),
+            fired_triggers: self.triggers.fired(),
+            player: PlayerState {
+                x: position.x,
+                y: position.y,
+                angle: self.player.angle(),
+                health: self.player.health(),
+                armor: self.player.armor(),
+                inventory: self.player.inventory().clone(),
+            },
+            weapons: self.weapons.clone(),
+            stats: self.stats.clone(),
+            explored: self.explored.clone(),
+            entities: self.entities.snapshot(),
+        };
+        save.write(path)
+    }
+
+    fn load_game(&mut self, path: &Path) -> Result<(), String> {
+        let save = SaveGame::read(path)?;
+        // everything is built first, the scene changes only if the whole save loads
+        let settings = match save.episode_level {
+            Some(index) => {
+                let mut settings = self.settings.clone();
+                settings.level = self.episode_level_info(index)?;
+                settings
+            }
+            None => {
+                let map = toml::Value::String(save.map).to_string();
+                self.settings.with_value("level.map", &map)?
+            }
+        };
+        let mut walls = Walls::default();
+        walls.prepare(&settings.level)?;
+        for (col, row, value) in save.changed_tiles {
+            walls.set_tile(col, row, value);
+        }
+        let mut triggers = Triggers::new(settings.level.triggers.len());
+        triggers.set_fired(&save.fired_triggers);
+        let explored = if save.explored.size() == walls.size() {
+            save.explored
+        } else {
+            let (cols, rows) = walls.size();
+            ExploredCells::new(cols, rows)
+        };
+        let entities = Entities::with_snapshots(&save.entities);
+        self.apply_settings(settings, Some(walls));
+        self.episode_level = save.episode_level;
+        self.triggers = triggers;
+        self.explored = explored;
+        self.entities = entities;
+        let PlayerState {
+            x,
+            y,
+            angle,
+            health,
+            armor,
+            inventory,
+        } = save.player;
+        self.player.setup(Float2d::new(x, y), angle);
+        self.player.reset_stats(health.max(1), armor);
+        self.player.set_inventory(inventory);
+        self.weapons = save.weapons;
+        self.stats = save.stats;
+        self.carried = None;
+        self.level_time = save.level_time;
+        self.has_session = true;
+        self.menu.clear();
+        self.controller_state.release_keys();
+        Ok(())
+    }
+
+    fn quicksave(&mut self) {
+        let path = PathBuf::from(QUICKSAVE_PATH);
+        match self.save_game(&path) {
+            Ok(_) => self.hud.show_message("Game saved"),
+            Err(err) => self.hud.show_message(format!("Save failed: {err}")),
+        }
+    }
+
+    fn quickload(&mut self) {
+        let path = PathBuf::from(QUICKSAVE_PATH);
+        match self.load_game(&path) {
+            Ok(_) => self.hud.show_message("Game loaded"),
+            Err(err) => self.hud.show_message(format!("Load failed: {err}")),
+        }
+    }
+
+    fn complete_level(&mut self) {
+        self.menu.reset(Screen::LevelComplete);
+        self.has_session = false;
+        self.controller_state.release_keys();
+    }
+
+    fn on_menu_action(&mut self, action: MenuAction) {
+        match action {
+            MenuAction::Open(Screen::LevelSelect) => {
+                self.menu.set_levels(find_levels(&self.settings.level.map));
+                self.menu.push(Screen::LevelSelect);
+            }
+            MenuAction::Open(screen) => self.menu.push(screen),
+            MenuAction::Back => self.menu.pop(),
+            MenuAction::Resume => self.menu.clear(),
+            MenuAction::NewGame if self.episode.is_some() => self.start_episode_level(0, None),
+            MenuAction::NewGame => self.start_level(None, None),
+            MenuAction::Restart => self.start_level(None, self.carried.clone()),
+            MenuAction::StartLevel(map) => {
+                self.episode_level = None;
+                self.start_level(Some(map), None);
+            }
+            MenuAction::StartEpisodeLevel(index) => self.start_episode_level(index, None),
+            MenuAction::NextLevel => self.start_next_level(),
+            MenuAction::MainMenu => self.menu.reset(Screen::MainMenu),
+            MenuAction::SetValue(key, value) => {
+                if let Err(err) = self.set_value(key, &value) {
+                    self.hud.show_message(err);
+                }
+            }
+            MenuAction::ToggleMinimap => {
+                self.controller_state.minimap_visible = !self.controller_state.minimap_visible
+            }
+            MenuAction::ToggleFullscreen => {
+                self.controller_state.fullscreen = !self.controller_state.fullscreen
+            }
+            MenuAction::Quit => self.state = State::Terminated,
+        }
+    }
+
+    fn setup_file_watcher(&mut self) {
+        self.file_watcher = self.settings.scene.hot_reload.then(|| {
+            let mut files = vec![
+                self.settings_source.path(),
+                Path::new(&self.settings.level.map),
+            ];
+            files.extend(self.settings.campaign.episode.as_deref().map(Path::new));
+            FileWatcher::with_files(&files)
+        });
+    }
+
+    /// Re-reads settings and map, keeps the current state if any of them is invalid
+    fn reload(&mut self) {
+        match self.try_reload() {
+            Ok(_) => {
+                println!("[INFO] settings and map reloaded");
+                self.hud.show_message("Reloaded");
+                self.error = None;
+            }
+            Err(err) => {
+                println!("[ERR] reload failed: {err}");
+                self.hud.show_message(format!("Reload failed: {err}"));
+                self.error = Some(err);
+            }
+        }
+        self.textures_outdated = true;
+    }
+
+    fn try_reload(&mut self) -> Result<(), String> {
+        let mut settings = self.settings_source.load().map_err(|err| err.to_string())?;
+        let episode = settings
+            .campaign
+            .episode
+            .as_ref()
+            .map(Episode::load)
+            .transpose()?;
+        // the episode's level replaces the `[level]` section
+        let level = episode
+            .as_ref()
+            .zip(self.episode_level)
+            .and_then(|(episode, index)| episode.levels.get(index));
+        if let Some(level) = level {
+            settings.level = level.clone();
+        }
+        let episode_level = self.episode_level.filter(|_| level.is_some());
+        let mut walls = Walls::default();
+        walls.prepare(&settings.level)?;
+        // entities, triggers and doors keep their state unless the level itself changed
+        let is_level_changed =
+            settings.level != self.settings.level || !walls.has_same_source(&self.walls);
+        // the scene changes only after everything is loaded
+        self.episode_level = episode_level;
+        self.episode = episode;
+        self.apply_settings(settings, is_level_changed.then_some(walls));
+        Ok(())
+    }
+
+    /// Applies settings to all components, `walls` replace the current map if provided
+    fn apply_settings(&mut self, settings: Settings, walls: Option<Walls>) {
+        if let Some(walls) = walls {
+            if walls.size() != self.explored.size() {
+                let (cols, rows) = walls.size();
+                self.explored = ExploredCells::new(cols, rows);
+            }
+            self.walls = walls;
+            self.entities = Entities::with_spawns(&settings.level.entities);
+            self.triggers = Triggers::new(settings.level.triggers.len());
+        }
+        // keep player position if it's still valid
+        let level_info = &settings.level;
+        if !self.walls.is_walkable(self.player.pos()) {
+            self.player.setup(
+                Float2d::new(level_info.player_x, level_info.player_y),
+                level_info.player_angle,
+            );
+        }
+        self.player.set_settings(&settings.player);
+        let (opts, current) = (&settings.scene, &self.settings.scene);
+        // rebuilding resets the dynamic resolution and the view, so only changes trigger it
+        let is_scaler_changed = opts.fps != current.fps
+            || opts.quality != current.quality
+            || opts.dynamic_resolution != current.dynamic_resolution;
+        if is_scaler_changed {
+            self.resolution_scaler = ResolutionScaler::new(opts);
+        }
+        let is_ray_caster_changed = opts.max_depth != current.max_depth
+            || opts.max_reflections != current.max_reflections
+            || opts.max_portals != current.max_portals;
+        if is_scaler_changed || is_ray_caster_changed {
+            self.ray_caster = RayCaster::new(opts, self.resolution_scaler.ray_divisor());
+        }
+        self.ray_caster
+            .resize(&self.screen_size, opts.horizontal_fov(&self.screen_size));
+        self.minimap
+            .set_settings(settings.minimap, self.screen_size);
+        self.hud.set_settings(settings.hud);
+        self.settings = settings;
+        self.setup_file_watcher();
+    }
+
+    /// Changes a single settings value at runtime
+    fn set_value(&mut self, key: &str, raw: &str) -> Result<(), String> {
+        let settings = self.settings.with_value(key, raw)?;
+        let walls = if settings.level.map != self.settings.level.map {
+            let mut walls = Walls::default();
+            walls.prepare(&settings.level)?;
+            Some(walls)
+        } else {
+            None
+        };
+        self.apply_settings(settings, walls);
+        Ok(())
+    }
+
+    fn execute_command(&mut self, line: &str) {
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        let result = match args.as_slice() {
+            ["help"] => Ok(format!("commands: {}", COMMANDS.join(", "))),
+            ["clear"] => {
+                self.console.clear();
+                Ok(String::new())
+            }
+            ["tp", x, y, rest @ ..] if rest.len() <= 1 => self.teleport(x, y, rest.first()),
+            ["fov", value] => self
+                .set_value("scene.fov", value)
+                .map(|_| format!("fov = {}", self.settings.scene.fov)),
+            ["noclip"] => {
+                let is_enabled = self.player.toggle_noclip();
+                Ok(format!("noclip {}", if is_enabled { "on" } else { "off" }))
+            }
+            ["reload"] => {
+                self.reload();
+                Ok(self.error.clone().unwrap_or_else(|| "reloaded".to_string()))
+            }
+            ["player"] => {
+                let pos = self.player.pos();
+                Ok(format!(
+                    "x: {:.3}, y: {:.3}, angle: {:.3} ({:.1} deg)",
+                    pos.x,
+                    pos.y,
+                    self.player.angle(),
+                    self.player.angle().to_degrees()
+                ))
+            }
+            ["set", key, value @ ..] if !value.is_empty() => {
+                let value = value.join(" ");
+                self.set_value(key, &value)
+                    .map(|_| format!("{key} = {}", self.settings.value(key).unwrap_or_default()))
+            }
+            ["get", key] => self
+                .settings
+                .value(key)
+                .map(|value| format!("{key} = {value}"))
+                .ok_or_else(|| format!("unknown variable `{key}`")),
+            ["cvars"] => Ok(self.settings.keys().join("  ")),
+            ["entities"] => Ok(self.entities.describe().join("\n")),
+            ["save", name @ ..] if name.len() <= 1 => Self::save_path(name.first().copied())
+                .and_then(|path| {
+                    self.save_game(&path)
+                        .map(|_| format!("saved to {}", path.display()))
+                }),
+            ["load", name @ ..] if name.len() <= 1 => Self::save_path(name.first().copied())
+                .and_then(|path| {
+                    self.load_game(&path)
+                        .map(|_| format!("loaded {}", path.display()))
+                }),
+            ["exit"] => {
+                self.complete_level();
+                Ok("level completed".to_string())
+            }
+            _ => Err(format!("unknown command '{line}', type 'help'")),
+        };
+        match result {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => output.lines().for_each(|line| self.console.print(line)),
+            Err(err) => self.console.print(format!("error: {err}")),
+        }
+    }
+
+    fn teleport(&mut self, x: &str, y: &str, angle: Option<&&str>) -> Result<String, String> {
+        let parse = |s: &str| {
+            s.parse::<Float>()
+                .map_err(|_| format!("invalid number '{s}'"))
+        };
+        let position = Float2d::new(parse(x)?, parse(y)?);
+        let angle = match angle {
+            Some(angle) => parse_angle(angle)?,
+            None => self.player.angle(),
+        };
+        self.player.setup(position, angle);
+        Ok(format!("teleported to {:.2} {:.2}", position.x, position.y))
+    }
+
+    fn complete_command(&mut self) {
+        let candidates = COMMANDS
+            .iter()
+            .map(|x| x.to_string())
+            .chain(self.settings.keys())
+            .collect::<Vec<_>>();
+        self.console.complete(&candidates);
     }
 
     pub fn process_events(&mut self, events: &[ControlEvent]) {
@@ -68,34 +641,480 @@ impl Scene {
                 ControlEvent::Keyboard {
                     key_code,
                     is_pressed,
-                } => self.controller_state.on_key_event(*key_code, *is_pressed),
+                } => self.on_key_event(*key_code, *is_pressed),
+                ControlEvent::TextInput { text } if self.console.is_open() => {
+                    self.console.on_text(text)
+                }
+                ControlEvent::TextInput { .. } => {}
                 ControlEvent::MouseMotion { x_rel, .. } => {
                     self.controller_state.mouse_x_relative = *x_rel;
                 }
+                ControlEvent::WindowResized { width, height } => self.on_resize(ScreenSize {
+                    width: *width,
+                    height: *height,
+                }),
             }
         }
     }
 
+    fn on_key_event(&mut self, key_code: i32, is_pressed: bool) {
+        if key_code == Console::KEYCODE_TOGGLE && is_pressed {
+            self.console.toggle();
+            self.controller_state.release_keys();
+            return;
+        }
+        if !self.console.is_open() && !self.menu.is_active() {
+            if key_code == Menu::KEYCODE_ESCAPE && is_pressed {
+                self.menu.push(Screen::Pause);
+                self.controller_state.release_keys();
+            } else {
+                self.controller_state.on_key_event(key_code, is_pressed);
+            }
+            return;
+        }
+        if !is_pressed {
+            return;
+        }
+        if !self.console.is_open() {
+            let context = MenuContext {
+                settings: &self.settings,
+                controller_state: &self.controller_state,
+                has_session: self.has_session,
+                level_time: self.level_time,
+                stats: &self.stats,
+                episode: self.episode.as_ref(),
+                episode_level: self.episode_level,
+            };
+            if let Some(action) = self.menu.on_key(key_code, &context) {
+                self.on_menu_action(action);
+            }
+            return;
+        }
+        if key_code == Menu::KEYCODE_ESCAPE {
+            self.console.toggle();
+            return;
+        }
+        match self.console.on_key(key_code) {
+            Some(ConsoleAction::Submit(line)) => self.execute_command(&line),
+            Some(ConsoleAction::Complete) => self.complete_command(),
+            None => {}
+        }
+    }
+
+    fn on_resize(&mut self, screen_size: ScreenSize) {
+        if screen_size == self.screen_size || screen_size.width == 0 || screen_size.height == 0 {
+            return;
+        }
+        self.screen_size = screen_size;
+        let fov = self.settings.scene.horizontal_fov(&screen_size);
+        self.ray_caster.resize(&screen_size, fov);
+        self.background.resize(screen_size);
+        self.minimap.resize(screen_size);
+        self.hud.resize(screen_size);
+    }
+
     pub fn update(&mut self) {
+        let is_files_changed = self
+            .file_watcher
+            .as_mut()
+            .is_some_and(|watcher| watcher.poll());
+        if self.controller_state.reload_requested || is_files_changed {
+            self.reload();
+        }
+        if self.controller_state.quicksave_requested {
+            self.quicksave();
+        }
+        if self.controller_state.quickload_requested {
+            self.quickload();
+        }
         let elapsed = self.time.elapsed().as_secs_f32();
-        // TODO: this design isn't good, need to improve
-        self.player
-            .update(elapsed, &self.controller_state, &self.walls);
+        // simulation is frozen while any menu or the console is open
+        if !self.menu.is_active() && !self.console.is_open() {
+            self.simulate(elapsed);
+        }
+        // frame counter and messages keep going while paused
+        self.hud.update(elapsed);
+        let floor_height = self.walls.floor_height(self.player.pos());
+        self.ray_caster
+            .set_eye_height(self.player.eye_height() + floor_height);
         self.ray_caster
             .update(self.player.pos(), self.player.angle(), &self.walls);
+        self.explored
+            .reveal(self.player.pos(), self.ray_caster.hits());
         self.background.update(self.player.angle());
         self.controller_state.reset_relative_values();
         self.time = Instant::now();
     }
 
+    fn simulate(&mut self, elapsed: Float) {
+        let is_alive = !self.player.is_dead();
+        let controller_state = &self.controller_state;
+        // TODO: this design isn't good, need to improve
+        if is_alive && !(controller_state.automap_visible && controller_state.automap_pan_mode) {
+            let (walls, entities) = (&self.walls, &self.entities);
+            let current = self.player.pos();
+            self.player.update(
+                elapsed,
+                controller_state,
+                |position| {
+                    walls.has_collision(position)
+                        || !walls.can_move(current, position)
+                        || entities.is_blocked(position, Player::RADIUS)
+                },
+                |from, to| walls.teleport(from, to),
+            );
+        }
+        let (player, entities) = (&self.player, &self.entities);
+        self.walls.update(elapsed, |col, row| {
+            overlaps_tile(player.pos(), Player::RADIUS, col, row)
+                || entities.occupies_tile(col, row)
+        });
+        self.automap.update(elapsed, controller_state, &self.player);
+        if !controller_state.automap_visible {
+            self.minimap.update(elapsed, controller_state);
+        }
+        let used_tile = if is_alive && self.controller_state.use_requested {
+            self.use_tile()
+        } else {
+            None
+        };
+        let mut events = Vec::new();
+        if is_alive {
+            if let Some(shot) = self.weapons.update(elapsed, &self.controller_state) {
+                events.extend(self.fire(shot));
+            }
+        }
+        events.extend(self.entities.update(
+            elapsed,
+            &self.walls,
+            self.player.pos(),
+            Player::RADIUS,
+        ));
+        let mut is_killed = false;
+        let mut killed_enemies = Vec::new();
+        for event in events {
+            match event {
+                EntityEvent::Message(text) => self.hud.show_message(text),
+                EntityEvent::PlayerHit { damage } => is_killed |= self.player.take_damage(damage),
+                EntityEvent::EnemyKilled { name } => {
+                    self.hud.show_message("Enemy killed");
+                    self.stats.kills += 1;
+                    killed_enemies.push(name);
+                }
+                EntityEvent::Pickup { id, pickup } if is_alive => {
+                    if self.pick_up(pickup) {
+                        self.entities.despawn(id);
+                        self.stats.items += 1;
+                        self.hud
+                            .show_message(format!("Picked up {}", pickup.item.name()));
+                    }
+                }
+                EntityEvent::Pickup { .. } => {}
+            }
+        }
+        let hazard_damage = self.hazard_damage();
+        if hazard_damage > 0.0 {
+            is_killed |= self.player.take_damage_over_time(hazard_damage, elapsed);
+        }
+        if is_killed {
+            self.hud.show_message("You died");
+        }
+        self.player.update_status(elapsed);
+        if self
+            .player
+            .dead_time()
+            .is_some_and(|time| time >= DEATH_SCREEN_DELAY)
+        {
+            self.menu.reset(Screen::Death);
+            self.has_session = false;
+            self.controller_state.release_keys();
+        }
+        let context = TriggerContext {
+            player_tile: (self.player.pos().x as usize, self.player.pos().y as usize),
+            used_tile,
+            killed: &killed_enemies,
+            enemies_left: self.entities.enemies_left(),
+            previous_time: self.level_time,
+            level_time: self.level_time + elapsed,
+        };
+        let actions = self
+            .triggers
+            .update(&self.settings.level.triggers, &context);
+        actions
+            .into_iter()
+            .for_each(|action| self.run_action(action));
+        if is_alive {
+            self.check_areas();
+        }
+        self.level_time += elapsed;
+    }
+
+    /// Counts secrets found by the player and finishes the level on exits
+    fn check_areas(&mut self) {
+        let position = self.player.pos();
+        let (col, row) = (position.x as usize, position.y as usize);
+        let level_info = &self.settings.level;
+        let secret = level_info
+            .secrets
+            .iter()
+            .position(|secret| secret.contains(col, row));
+        if let Some(secret) = secret.filter(|secret| !self.stats.secrets.contains(secret)) {
+            self.stats.secrets.push(secret);
+            self.hud.show_message("You found a secret!");
+        }
+        if self.has_session && level_info.exits.iter().any(|exit| exit.contains(col, row)) {
+            self.complete_level();
+        }
+    }
+
+    fn run_action(&mut self, action: TriggerAction) {
+        match action {
+            TriggerAction::OpenDoor { col, row } => self.walls.clear_tile(col, row),
+            TriggerAction::SetTile { col, row, tile } => self.walls.set_tile(col, row, tile),
+            TriggerAction::Spawn(spawn) => {
+                self.entities.spawn_from(&spawn);
+            }
+            TriggerAction::Message { text } => self.hud.show_message(text),
+            TriggerAction::Sound { name } => println!("[INFO] sound: {name}"),
+            TriggerAction::EndLevel => self.complete_level(),
+        }
+    }
+
+    /// Applies the item, returns false if the player can't take it
+    fn pick_up(&mut self, pickup: Pickup) -> bool {
+        let Pickup { item, amount } = pickup;
+        if let Some(color) = item.key_color() {
+            return self.player.inventory_mut().add_key(color);
+        }
+        match item {
+            ItemKind::Health => self.player.heal(amount),
+            ItemKind::Armor => self.player.add_armor(amount),
+            ItemKind::Ammo => self.weapons.add_ammo(WeaponKind::Pistol, amount as u32),
+            ItemKind::Rockets => self.weapons.add_ammo(WeaponKind::Launcher, amount as u32),
+            ItemKind::Treasure => {
+                self.player.inventory_mut().add_score(amount as u32);
+                true
+            }
+            ItemKind::RedKey | ItemKind::BlueKey | ItemKind::YellowKey => false,
+        }
+    }
+
+    /// Uses the wall tile the player is looking at, doors open if the player has their keys.
+    /// Returns the tile for use triggers
+    fn use_tile(&mut self) -> Option<(usize, usize)> {
+        let origin = self.player.pos();
+        let wall = ray::GridRay::new(origin, self.player.angle())
+            .take_while(|step| step.depth <= USE_RANGE)
+            .find(|step| !self.walls.is_open(step.col, step.row));
+        let step = wall?;
+        let (col, row) = (step.col as usize, step.row as usize);
+        let door = self
+            .settings
+            .level
+            .doors
+            .iter()
+            .find(|door| door.col == col && door.row == row);
+        match door.and_then(|door| door.key) {
+            Some(color) if !self.player.inventory().has_key(color) => {
+                self.hud.show_message(format!("You need the {color} key"));
+            }
+            _ if door.is_some() => self.walls.clear_tile(col, row),
+            _ => self.push_wall(col, row),
+        }
+        Some((col, row))
+    }
+
+    /// Slides the secret wall away from the player, found push walls are counted
+    /// as secrets after the level's secret areas
+    fn push_wall(&mut self, col: usize, row: usize) {
+        let level_info = &self.settings.level;
+        let Some(index) = level_info
+            .push_walls
+            .iter()
+            .position(|push_wall| push_wall.col == col && push_wall.row == row)
+        else {
+            return;
+        };
+        let secret = level_info.secrets.len() + index;
+        if self.stats.secrets.contains(&secret) {
+            return;
+        }
+        let offset = Float2d::new(col as Float + 0.5, row as Float + 0.5) - self.player.pos();
+        let direction = if offset.x.abs() > offset.y.abs() {
+            (offset.x.signum() as i32, 0)
+        } else {
+            (0, offset.y.signum() as i32)
+        };
+        let distance = level_info.push_walls[index].distance;
+        if self.walls.push_tile(col, row, direction, distance) {
+            self.stats.secrets.push(secret);
+            self.hud.show_message("You found a secret!");
+        }
+    }
+
+    /// Hazard tiles, doors still closed and items left on the level for the automap
+    fn map_markers(&self) -> Vec<MapMarker> {
+        let hazards = self
+            .settings
+            .level
+            .hazards
+            .iter()
+            .flat_map(|hazard| hazard.area.tiles())
+            .map(|(col, row)| MapMarker {
+                position: Float2d::new(col as Float + 0.5, row as Float + 0.5),
+                size: 1.0,
+                color: (60, 110, 40),
+            })
+            .filter(|marker| self.walls.is_walkable(marker.position));
+        let doors = self
+            .settings
+            .level
+            .doors
+            .iter()
+            .filter(|door| !self.walls.is_open(door.col as i32, door.row as i32))
+            .map(|door| MapMarker {
+                position: Float2d::new(door.col as Float + 0.5, door.row as Float + 0.5),
+                size: 1.0,
+                color: door.key.map(|key| key.rgb()).unwrap_or((150, 110, 60)),
+            });
+        let items = self.entities.items().map(|(position, item)| MapMarker {
+            position,
+            size: 0.4,
+            color: item.color(),
+        });
+        hazards.chain(doors).chain(items).collect()
+    }
+
+    /// Damage per second dealt by hazards under the player
+    fn hazard_damage(&self) -> Float {
+        let position = self.player.pos();
+        let (col, row) = (position.x as usize, position.y as usize);
+        self.settings
+            .level
+            .hazards
+            .iter()
+            .filter(|hazard| hazard.area.contains(col, row))
+            .map(|hazard| hazard.damage)
+            .sum()
+    }
+
+    /// Traces hitscan shots or launches projectiles from the player's position
+    fn fire(&mut self, shot: Shot) -> Option<EntityEvent> {
+        let origin = self.player.pos();
+        let angle = self.player.angle();
+        let direction = Float2d::new(angle.cos(), angle.sin());
+        match shot {
+            Shot::Hitscan { damage, range } => {
+                let wall_depth = ray::cast(&self.walls, origin, angle, range);
+                let target = self
+                    .entities
+                    .raycast(origin, angle, wall_depth.unwrap_or(range));
+                let (depth, event) = match (target, wall_depth) {
+                    (Some((id, depth)), _) => (depth, self.entities.damage(id, damage, origin)),
+                    (None, Some(depth)) => (depth, None),
+                    (None, None) => return None,
+                };
+                let depth = (depth - HIT_OFFSET).max(0.0);
+                self.entities
+                    .spawn_spark(origin + Float2d::new(direction.x * depth, direction.y * depth));
+                event
+            }
+            Shot::Projectile { damage, speed } => {
+                let offset = Player::RADIUS;
+                self.entities.spawn_projectile(
+                    origin + Float2d::new(direction.x * offset, direction.y * offset),
+                    Float2d::new(direction.x * speed, direction.y * speed),
+                    damage,
+                );
+                None
+            }
+        }
+    }
+
     pub fn draw(&self, commands: &mut Vec<DrawCommand>) {
         // TODO: this design isn't good, need to improve
         self.background.draw(commands);
         self.ray_caster.draw(commands);
-        // TODO: refactor as mini map
-        if self.controller_state.minimap_visible {
-            self.walls.draw(commands);
-            self.player.draw(commands);
+        // sprites stand on the sector floors
+        let player = std::iter::once((self.player.pos(), self.player.sprite()));
+        let sprites = self
+            .entities
+            .sprites()
+            .chain(player)
+            .map(|(position, mut sprite)| {
+                sprite.elevation += self.walls.floor_height(position);
+                (position, sprite)
+            });
+        self.ray_caster.draw_sprites(commands, sprites);
+        if self.error.is_some() {
+            draw_border(
+                commands,
+                self.screen_size,
+                (255, 0, 0),
+                ERROR_FRAME_THICKNESS,
+            );
+        }
+        if self.controller_state.automap_visible {
+            self.automap.draw(
+                commands,
+                self.screen_size,
+                &self.walls,
+                &self.explored,
+                &self.map_markers(),
+                &self.player,
+            );
+        } else if self.controller_state.minimap_visible {
+            self.minimap.draw(
+                commands,
+                &self.walls,
+                &self.explored,
+                &self.player,
+                &self.ray_caster,
+            );
+        }
+        if !self.controller_state.automap_visible && !self.player.is_dead() {
+            self.weapons.draw(commands, self.screen_size);
+        }
+        let status = HudStatus {
+            health: Some(self.player.health()),
+            armor: Some(self.player.armor()),
+            ammo: Some(self.weapons.ammo()),
+            weapon: Some(self.weapons.name()),
+            score: Some(self.player.inventory().score()),
+            keys: self.player.inventory().keys().to_vec(),
+            hurt_flash: self.player.hurt_flash(),
+        };
+        self.hud
+            .draw(commands, &status, self.resolution_scaler.quality());
+        let context = MenuContext {
+            settings: &self.settings,
+            controller_state: &self.controller_state,
+            has_session: self.has_session,
+            level_time: self.level_time,
+            stats: &self.stats,
+            episode: self.episode.as_ref(),
+            episode_level: self.episode_level,
+        };
+        self.menu.draw(commands, self.screen_size, &context);
+        self.console.draw(commands, self.screen_size);
+    }
+
+    /// Shows the error in the console, e.g. a texture which failed to reload
+    pub fn report_error(&mut self, err: &str) {
+        self.console.print(format!("error: {err}"));
+    }
+
+    /// Returns true once after each reload to let the renderer reload textures
+    pub fn take_textures_outdated(&mut self) -> bool {
+        std::mem::take(&mut self.textures_outdated)
+    }
+
+    /// Should be called when frame is ready to be presented
+    pub fn on_frame_rendered(&mut self, frame_time: f32) {
+        self.hud.set_frame_time(frame_time);
+        if self.resolution_scaler.update(frame_time) {
+            self.ray_caster
+                .set_ray_divisor(self.resolution_scaler.ray_divisor());
         }
     }
 
@@ -107,8 +1126,20 @@ impl Scene {
         self.state = State::Terminated;
     }
 
-    pub fn window_size(&self) -> Size2d<u32> {
-        self.settings.scene.screen_size()
+    pub fn window_size(&self) -> ScreenSize {
+        self.screen_size
+    }
+
+    pub fn is_resizable(&self) -> bool {
+        self.settings.scene.resizable
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.controller_state.fullscreen
+    }
+
+    pub fn fullscreen_mode(&self) -> FullscreenMode {
+        self.settings.scene.fullscreen_mode
     }
 
     pub fn target_fps(&self) -> usize {
@@ -0,0 +1,271 @@
+use std::{fs, path::Path};
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    common::{Float, Float2d},
+    walls::distance,
+};
+
+/// Ray traversal stops after crossing that many portals
+const MAX_PORTALS: usize = 64;
+/// Distance the player keeps from solid walls
+const WALL_MARGIN: Float = 0.1;
+/// The highest floor step the player climbs
+const MAX_STEP: Float = 0.35;
+/// The lowest opening between the floor and the ceiling the player fits in
+const MIN_HEADROOM: Float = 0.6;
+const TOL: Float = 1e-4;
+
+/// Edge of the sector from its vertex to the next wall's one
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectorWall {
+    /// Index in the map's vertices
+    pub vertex: usize,
+    /// Neighbour sector seen through the wall, the wall is solid if not set
+    pub portal: Option<usize>,
+    /// Texture of the solid wall or the steps between floors and ceilings, default: 1
+    #[serde(default = "default_texture")]
+    pub texture: i32,
+}
+
+fn default_texture() -> i32 {
+    1
+}
+
+/// Convex room with flat floor and ceiling, heights are in wall units
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sector {
+    #[serde(default)]
+    pub floor: Float,
+    #[serde(default = "default_ceiling")]
+    pub ceiling: Float,
+    #[serde(default = "default_floor_color")]
+    pub floor_color: (u8, u8, u8),
+    #[serde(default = "default_ceiling_color")]
+    pub ceiling_color: (u8, u8, u8),
+    /// Edges in the polygon order
+    pub walls: Vec<SectorWall>,
+}
+
+fn default_ceiling() -> Float {
+    1.0
+}
+
+fn default_floor_color() -> (u8, u8, u8) {
+    (30, 30, 30)
+}
+
+fn default_ceiling_color() -> (u8, u8, u8) {
+    (60, 60, 70)
+}
+
+/// Part of the ray inside of the sector, depths are distances along the ray
+pub struct SectorSpan {
+    pub sector: usize,
+    pub entry: Float,
+    pub exit: Float,
+    /// wall the ray leaves the sector through
+    pub wall: usize,
+}
+
+/// Level made of convex sectors connected by portals, an alternative to the grid map
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectorMap {
+    /// Map coordinates of the walls' ends, the same units as the grid tiles
+    pub vertices: Vec<(Float, Float)>,
+    pub sectors: Vec<Sector>,
+}
+
+impl SectorMap {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let map = toml::from_str::<SectorMap>(&content).map_err(|err| err.to_string())?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.sectors.is_empty() {
+            return Err("map has no sectors".to_string());
+        }
+        if self.vertices.iter().any(|(x, y)| *x < 1.0 || *y < 1.0) {
+            return Err("vertex coordinates must be at least 1".to_string());
+        }
+        for (i, sector) in self.sectors.iter().enumerate() {
+            if sector.walls.len() < 3 {
+                return Err(format!("sector #{i} has less than 3 walls"));
+            }
+            if sector.ceiling <= sector.floor {
+                return Err(format!("sector #{i} ceiling isn't above its floor"));
+            }
+            for wall in &sector.walls {
+                if wall.vertex >= self.vertices.len() {
+                    return Err(format!("sector #{i} has unknown vertex {}", wall.vertex));
+                }
+                if wall
+                    .portal
+                    .is_some_and(|portal| portal >= self.sectors.len())
+                {
+                    return Err(format!("sector #{i} has portal to unknown sector"));
+                }
+            }
+            let turns = (0..sector.walls.len())
+                .map(|wall| {
+                    let (start, end) = self.wall(i, wall);
+                    let (_, next) = self.wall(i, (wall + 1) % sector.walls.len());
+                    (end - start).cross(&(next - end))
+                })
+                .collect::<Vec<_>>();
+            if !turns.iter().all(|turn| *turn >= 0.0) && !turns.iter().all(|turn| *turn <= 0.0) {
+                return Err(format!("sector #{i} isn't convex"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns ends of the sector's wall
+    pub fn wall(&self, sector: usize, wall: usize) -> (Float2d, Float2d) {
+        let walls = &self.sectors[sector].walls;
+        let vertex = |index: usize| {
+            let (x, y) = self.vertices[walls[index % walls.len()].vertex];
+            Float2d::new(x, y)
+        };
+        (vertex(wall), vertex(wall + 1))
+    }
+
+    /// Returns the sector containing the point
+    pub fn sector_at(&self, point: Float2d) -> Option<usize> {
+        (0..self.sectors.len()).find(|sector| {
+            let sides = (0..self.sectors[*sector].walls.len()).map(|wall| {
+                let (start, end) = self.wall(*sector, wall);
+                (end - start).cross(&(point - start))
+            });
+            let (mut has_left, mut has_right) = (false, false);
+            for side in sides {
+                has_left |= side < -TOL;
+                has_right |= side > TOL;
+            }
+            !(has_left && has_right)
+        })
+    }
+
+    /// Floor height at the point, 0 outside of the sectors
+    pub fn floor_height(&self, point: Float2d) -> Float {
+        self.sector_at(point)
+            .map_or(0.0, |sector| self.sectors[sector].floor)
+    }
+
+    /// Checks if the point is outside of the sectors or too close to a solid wall
+    pub fn has_collision(&self, point: Float2d) -> bool {
+        let Some(sector) = self.sector_at(point) else {
+            return true;
+        };
+        let walls = &self.sectors[sector].walls;
+        (0..walls.len()).any(|wall| {
+            walls[wall].portal.is_none() && distance(point, self.wall(sector, wall)) < WALL_MARGIN
+        })
+    }
+
+    /// Checks if the player can step from one point to another, steps and low ceilings stop them
+    pub fn can_move(&self, from: Float2d, to: Float2d) -> bool {
+        let (Some(from), Some(to)) = (self.sector_at(from), self.sector_at(to)) else {
+            return false;
+        };
+        let (from, to) = (&self.sectors[from], &self.sectors[to]);
+        to.floor - from.floor <= MAX_STEP && to.ceiling - to.floor >= MIN_HEADROOM
+    }
+
+    /// Map size in tiles with a margin of walls around the sectors
+    pub fn size(&self) -> (usize, usize) {
+        let (max_x, max_y) = self
+            .vertices
+            .iter()
+            .fold((0.0, 0.0), |(max_x, max_y): (Float, Float), (x, y)| {
+                (max_x.max(*x), max_y.max(*y))
+            });
+        (max_x.ceil() as usize + 1, max_y.ceil() as usize + 1)
+    }
+
+    /// Grid approximation of the map for path finding and automap,
+    /// tiles with centres outside of the sectors are walls
+    pub fn rasterize(&self) -> Vec<Vec<i32>> {
+        let (cols, rows) = self.size();
+        (0..rows)
+            .map(|row| {
+                (0..cols)
+                    .map(|col| {
+                        let center = Float2d::new(col as Float + 0.5, row as Float + 0.5);
+                        i32::from(self.sector_at(center).is_none())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Follows the ray from the sector through portals until a solid wall
+    pub fn trace(&self, sector: usize, origin: Float2d, direction: Float2d) -> Vec<SectorSpan> {
+        let mut spans = Vec::new();
+        let mut current = Some(sector);
+        let mut entry = 0.0;
+        while let Some(sector) = current.filter(|_| spans.len() < MAX_PORTALS) {
+            // the ray leaves a convex sector through the farthest wall it crosses
+            let exit = (0..self.sectors[sector].walls.len())
+                .filter_map(|wall| {
+                    let depth = intersect(origin, direction, self.wall(sector, wall))?;
+                    (depth >= entry - TOL).then_some((wall, depth))
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            let Some((wall, exit)) = exit else {
+                break;
+            };
+            spans.push(SectorSpan {
+                sector,
+                entry,
+                exit,
+                wall,
+            });
+            current = self.sectors[sector].walls[wall].portal;
+            entry = exit;
+        }
+        spans
+    }
+
+    /// Returns distance to the first solid wall or closed portal along the ray within `max_depth`
+    pub fn cast(&self, origin: Float2d, angle: Float, max_depth: Float) -> Option<Float> {
+        let sector = self.sector_at(origin)?;
+        let direction = Float2d::new(angle.cos(), angle.sin());
+        self.trace(sector, origin, direction)
+            .into_iter()
+            .take_while(|span| span.entry <= max_depth)
+            .find(|span| {
+                let Some(next) = self.sectors[span.sector].walls[span.wall].portal else {
+                    return true;
+                };
+                let (sector, next) = (&self.sectors[span.sector], &self.sectors[next]);
+                next.ceiling.min(sector.ceiling) <= next.floor.max(sector.floor)
+            })
+            .map(|span| span.exit)
+            .filter(|depth| *depth <= max_depth)
+    }
+}
+
+/// Returns depth along the ray where it crosses the segment
+fn intersect(
+    origin: Float2d,
+    direction: Float2d,
+    (start, end): (Float2d, Float2d),
+) -> Option<Float> {
+    let wall = end - start;
+    let denominator = direction.cross(&wall);
+    if denominator.abs() < 1e-6 {
+        return None;
+    }
+    let to_start = start - origin;
+    let depth = to_start.cross(&wall) / denominator;
+    let offset = to_start.cross(&direction) / denominator;
+    (depth >= 0.0 && (-TOL..=1.0 + TOL).contains(&offset)).then_some(depth)
+}
@@ -1,32 +1,419 @@
-use std::{fs, io, path::Path};
+use std::{
+    env,
+    f32::consts::PI,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use serde_derive::Deserialize;
+use serde::{de, Deserializer};
+use serde_derive::{Deserialize, Serialize};
 
-use crate::common::{ScreenSize, Size2d};
+use crate::{
+    common::{Float, ScreenSize, Size2d},
+    entity::EntityKind,
+    inventory::{ItemKind, KeyColor},
+    player::{MAX_ARMOR, MAX_HEALTH},
+    triggers::{Trigger, TriggerAction, TriggerEvent},
+    walls::TileShape,
+};
 
-#[derive(Default, Deserialize)]
+/// Environment variables with this prefix override settings values,
+/// sections are separated with double underscore, e.g. `RAYCASTER_SCENE__FPS=30`
+const ENV_PREFIX: &str = "RAYCASTER_";
+const ENV_SEPARATOR: &str = "__";
+
+#[derive(Debug)]
+pub struct SettingsError {
+    pub file: String,
+    pub key: Option<String>,
+    pub reason: String,
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "{}: `{key}` {}", self.file, self.reason),
+            None => write!(f, "{}: {}", self.file, self.reason),
+        }
+    }
+}
+
+/// Settings file with the overrides to apply on top of it
+#[derive(Clone)]
+pub struct SettingsSource {
+    pub config: PathBuf,
+    /// dot separated keys with raw values, e.g. (`scene.fps`, `30`)
+    pub overrides: Vec<(String, String)>,
+}
+
+impl SettingsSource {
+    pub fn load(&self) -> Result<Settings, SettingsError> {
+        Settings::with_overrides(&self.config, &self.overrides)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.config
+    }
+}
+
+#[derive(Default, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Settings {
+    #[serde(default)]
     pub scene: SceneSettings,
+    #[serde(default)]
     pub player: PlayerSettings,
+    #[serde(default)]
+    pub minimap: MinimapSettings,
+    #[serde(default)]
+    pub hud: HudSettings,
+    #[serde(default)]
+    pub campaign: CampaignSettings,
     pub level: LevelInfo,
 }
 
 impl Settings {
-    pub fn with_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let content = fs::read_to_string(path)?;
-        let config = toml::from_str(&content)?;
-        Ok(config)
+    /// Reads settings file and applies overrides on top of it.
+    /// Environment variables go first, so explicit `overrides` take precedence
+    pub fn with_overrides<P: AsRef<Path>>(
+        path: P,
+        overrides: &[(String, String)],
+    ) -> Result<Self, SettingsError> {
+        Self::with_sources(path, &env_overrides(env::vars()), overrides)
+    }
+
+    /// Reads settings file and applies `env` overrides, then the explicit ones
+    fn with_sources<P: AsRef<Path>>(
+        path: P,
+        env: &[(String, String)],
+        overrides: &[(String, String)],
+    ) -> Result<Self, SettingsError> {
+        let file = path.as_ref().display().to_string();
+        let error = |key: Option<String>, reason: String| SettingsError {
+            file: file.clone(),
+            key,
+            reason,
+        };
+        let content = fs::read_to_string(&path).map_err(|err| error(None, err.to_string()))?;
+        let mut value =
+            toml::from_str::<toml::Value>(&content).map_err(|err| error(None, err.to_string()))?;
+        for (key, raw) in env.iter().chain(overrides) {
+            apply_override(&mut value, key, raw)
+                .map_err(|reason| error(Some(key.clone()), reason))?;
+        }
+        let settings = value
+            .try_into::<Settings>()
+            .map_err(|err| error(None, err.to_string()))?;
+        settings
+            .validate()
+            .map_err(|(key, reason)| error(Some(key.to_string()), reason))?;
+        Ok(settings)
+    }
+
+    /// Returns a copy of the settings with a single value changed, see [`apply_override`]
+    pub fn with_value(&self, key: &str, raw: &str) -> Result<Self, String> {
+        let mut value = toml::Value::try_from(self).map_err(|err| err.to_string())?;
+        apply_override(&mut value, key, raw).map_err(|reason| format!("`{key}` {reason}"))?;
+        let settings = value
+            .try_into::<Settings>()
+            .map_err(|err| err.to_string())?;
+        settings
+            .validate()
+            .map_err(|(key, reason)| format!("`{key}` {reason}"))?;
+        Ok(settings)
+    }
+
+    /// Returns value for dot separated `key` like `scene.fps`
+    pub fn value(&self, key: &str) -> Option<String> {
+        let mut current = toml::Value::try_from(self).ok()?;
+        for name in key.split('.') {
+            current = current.as_table_mut()?.remove(name)?;
+        }
+        Some(current.to_string())
+    }
+
+    /// Returns dot separated keys of all values
+    pub fn keys(&self) -> Vec<String> {
+        let Ok(toml::Value::Table(table)) = toml::Value::try_from(self) else {
+            return Vec::new();
+        };
+        let mut keys = Vec::new();
+        for (section, values) in table {
+            let Some(values) = values.as_table() else {
+                continue;
+            };
+            keys.extend(values.keys().map(|key| format!("{section}.{key}")));
+        }
+        keys
+    }
+
+    fn validate(&self) -> Result<(), (&'static str, String)> {
+        let scene = &self.scene;
+        check_range("scene.tile_size", scene.tile_size, 1, 64)?;
+        check_range("scene.screen_width", scene.screen_width, 64, 16384)?;
+        check_range("scene.screen_height", scene.screen_height, 64, 16384)?;
+        check_range("scene.fps", scene.fps, 1, 1000)?;
+        check_range("scene.max_depth", scene.max_depth, 1, 10000)?;
+        check_range("scene.max_reflections", scene.max_reflections, 0, 16)?;
+        check_range("scene.max_portals", scene.max_portals, 0, 16)?;
+        if !(scene.fov > 0.0 && scene.fov < PI) {
+            return Err((
+                "scene.fov",
+                format!(
+                    "must be between 0 and 180 degrees, got {:.2} radians; use \"60deg\" to specify degrees",
+                    scene.fov
+                ),
+            ));
+        }
+        let player = &self.player;
+        check_range(
+            "player.player_movement_speed",
+            player.player_movement_speed,
+            0.0,
+            100.0,
+        )?;
+        check_range(
+            "player.player_rotation_speed",
+            player.player_rotation_speed,
+            0.0,
+            100.0,
+        )?;
+        let minimap = &self.minimap;
+        check_range("minimap.size", minimap.size, 32, 4096)?;
+        check_range("minimap.margin", minimap.margin, 0, 1000)?;
+        let hud = &self.hud;
+        check_range("hud.scale", hud.scale, 1, 16)?;
+        check_range("hud.message_time", hud.message_time, 0.0, 3600.0)?;
+        if let Some(episode) = &self.campaign.episode {
+            if episode.is_empty() {
+                return Err((
+                    "campaign.episode",
+                    "must be a path to the episode file".to_string(),
+                ));
+            }
+        }
+        self.level.validate()
+    }
+}
+
+impl LevelInfo {
+    pub fn validate(&self) -> Result<(), (&'static str, String)> {
+        let level = self;
+        if level.map.is_empty() {
+            return Err(("level.map", "must be a path to the map file".to_string()));
+        }
+        check_range("level.player_x", level.player_x, 0.0, Float::MAX)?;
+        check_range("level.player_y", level.player_y, 0.0, Float::MAX)?;
+        check_range("level.health", level.health, 1, MAX_HEALTH)?;
+        check_range("level.armor", level.armor, 0, MAX_ARMOR)?;
+        for hazard in &level.hazards {
+            check_range("level.hazards.damage", hazard.damage, 0.0, 1000.0)?;
+        }
+        for trigger in &level.triggers {
+            if trigger.event == TriggerEvent::Timer {
+                let time = trigger.time.unwrap_or_default();
+                check_range("level.triggers.time", time, 0.001, Float::MAX)?;
+            }
+            if trigger.actions.is_empty() {
+                return Err(("level.triggers.actions", "must not be empty".to_string()));
+            }
+        }
+        for push_wall in &level.push_walls {
+            check_range("level.push_walls.distance", push_wall.distance, 1, 2)?;
+        }
+        for tile in &level.tiles {
+            check_range("level.tiles.tile", tile.tile, 1, i32::MAX)?;
+            check_range("level.tiles.height", tile.height, 0.01, 16.0)?;
+            check_range("level.tiles.floor", tile.floor, 0.0, 16.0)?;
+            // reflections fill the whole column
+            if tile.mirror && (tile.masked || tile.floor > 0.0 || tile.height < 1.0) {
+                return Err((
+                    "level.tiles.mirror",
+                    format!(
+                        "tile {} is masked, lower than a wall or above the floor",
+                        tile.tile
+                    ),
+                ));
+            }
+        }
+        for spawn in &level.entities {
+            spawn.validate("level.entities.amount")?;
+        }
+        let trigger_spawns = level.triggers.iter().flat_map(|trigger| {
+            trigger.actions.iter().filter_map(|action| match action {
+                TriggerAction::Spawn(spawn) => Some(spawn),
+                _ => None,
+            })
+        });
+        for spawn in trigger_spawns {
+            spawn.validate("level.triggers.actions.amount")?;
+        }
+        Ok(())
+    }
+}
+
+impl EntitySpawn {
+    /// `key` names the spawn's amount in error messages
+    fn validate(&self, key: &'static str) -> Result<(), (&'static str, String)> {
+        if let Some(amount) = self.amount {
+            check_range(key, amount, 0, 10000)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_range<T: PartialOrd + Display>(
+    key: &'static str,
+    value: T,
+    min: T,
+    max: T,
+) -> Result<(), (&'static str, String)> {
+    if value >= min && value <= max {
+        return Ok(());
     }
+    Err((key, format!("must be in range {min}..={max}, got {value}")))
 }
 
-#[derive(Default, Deserialize)]
+/// Turns environment variables with the settings prefix into overrides
+fn env_overrides(vars: impl Iterator<Item = (String, String)>) -> Vec<(String, String)> {
+    vars.filter_map(|(name, value)| {
+        let key = name
+            .strip_prefix(ENV_PREFIX)?
+            .to_lowercase()
+            .replace(ENV_SEPARATOR, ".");
+        Some((key, value))
+    })
+    .collect()
+}
+
+/// Sets value for dot separated `key` like `scene.fps`.
+/// `raw` value is parsed as TOML value and treated as string if it isn't valid TOML
+pub fn apply_override(value: &mut toml::Value, key: &str, raw: &str) -> Result<(), String> {
+    let parsed = toml::from_str::<toml::Value>(&format!("value = {raw}"))
+        .ok()
+        .and_then(|table| table.get("value").cloned())
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+    let mut path = key.split('.').peekable();
+    let mut current = value;
+    while let Some(name) = path.next() {
+        if name.is_empty() {
+            return Err("is not a valid settings key".to_string());
+        }
+        let Some(table) = current.as_table_mut() else {
+            return Err(format!("can't be set, `{name}` isn't inside of a section"));
+        };
+        if path.peek().is_none() {
+            table.insert(name.to_string(), parsed);
+            return Ok(());
+        }
+        current = table
+            .entry(name.to_string())
+            .or_insert_with(|| toml::Value::Table(Default::default()));
+    }
+    Err("is not a valid settings key".to_string())
+}
+
+/// Accepts angle as a number of radians or a string with `deg` or `rad` suffix
+fn deserialize_angle<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Float, D::Error> {
+    struct AngleVisitor;
+
+    impl<'de> de::Visitor<'de> for AngleVisitor {
+        type Value = Float;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("angle in radians or a string like \"60deg\"")
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<Float, E> {
+            Ok(value as Float)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<Float, E> {
+            Ok(value as Float)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Float, E> {
+            parse_angle(value).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_any(AngleVisitor)
+}
+
+pub fn parse_angle(s: &str) -> Result<Float, String> {
+    let s = s.trim();
+    let (number, factor) = if let Some(number) = s.strip_suffix("deg") {
+        (number, PI / 180.0)
+    } else if let Some(number) = s.strip_suffix("rad") {
+        (number, 1.0)
+    } else {
+        (s, 1.0)
+    };
+    number
+        .trim()
+        .parse::<Float>()
+        .map(|value| value * factor)
+        .map_err(|_| format!("invalid angle '{s}', expected radians or a value like \"60deg\""))
+}
+
+/// Scene settings, every field is optional
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct SceneSettings {
+    /// Initial minimap tile size in pixels, zoomed with +/- keys, default: 5
     pub tile_size: usize,
+    /// Window height in pixels, default: 720
     pub screen_height: usize,
+    /// Window width in pixels, default: 1280
     pub screen_width: usize,
+    /// Target frames per second, default: 60
     pub fps: usize,
+    /// Max ray length in tiles, default: 50
     pub max_depth: usize,
-    pub fov: f32,
+    /// Times a ray bounces off mirrors, the last mirror is drawn as a wall, default: 4
+    pub max_reflections: usize,
+    /// Times a ray passes portals, the last portal is drawn as a wall, default: 4
+    pub max_portals: usize,
+    /// Horizontal field of view in radians or degrees ("60deg"), default: 60 degrees
+    #[serde(deserialize_with = "deserialize_angle")]
+    pub fov: Float,
+    /// Default: high
+    pub quality: Quality,
+    /// Default: false
+    pub dynamic_resolution: bool,
+    /// Default: true
+    pub resizable: bool,
+    /// Default: false
+    pub fullscreen: bool,
+    /// Default: borderless
+    pub fullscreen_mode: FullscreenMode,
+    /// Default: horizontal
+    pub fov_policy: FovPolicy,
+    /// Default: false
+    pub hot_reload: bool,
+}
+
+impl Default for SceneSettings {
+    fn default() -> Self {
+        Self {
+            tile_size: 5,
+            screen_height: 720,
+            screen_width: 1280,
+            fps: 60,
+            max_depth: 50,
+            max_reflections: 4,
+            max_portals: 4,
+            fov: PI / 3.0,
+            quality: Quality::default(),
+            dynamic_resolution: false,
+            resizable: true,
+            fullscreen: false,
+            fullscreen_mode: FullscreenMode::default(),
+            fov_policy: FovPolicy::default(),
+            hot_reload: false,
+        }
+    }
 }
 
 impl SceneSettings {
@@ -36,17 +423,477 @@ impl SceneSettings {
             height: self.screen_height as u32,
         }
     }
+
+    /// Returns horizontal field of view for the given screen size.
+    /// `fov` value is treated as horizontal FOV for the configured screen size
+    pub fn horizontal_fov(&self, size: &ScreenSize) -> Float {
+        match self.fov_policy {
+            FovPolicy::Horizontal => self.fov,
+            FovPolicy::Vertical => {
+                let half_tan = (0.5 * self.fov).tan();
+                let vertical_half_tan =
+                    half_tan * self.screen_height as Float / self.screen_width as Float;
+                let aspect = size.width as Float / size.height.max(1) as Float;
+                2.0 * (vertical_half_tan * aspect).atan()
+            }
+        }
+    }
+}
+
+/// Defines which field of view stays fixed when the window's aspect ratio changes
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FovPolicy {
+    #[default]
+    Horizontal,
+    Vertical,
 }
 
-#[derive(Default, Deserialize)]
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenMode {
+    /// Window covers the desktop without changing the display mode
+    #[default]
+    Borderless,
+    /// Display mode is switched to the window size
+    Exclusive,
+}
+
+/// Render quality level, defines how many screen columns are covered by a single ray
+#[derive(Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    Low,
+    Medium,
+    #[default]
+    High,
+    Ultra,
+}
+
+impl Quality {
+    pub fn ray_divisor(&self) -> usize {
+        match self {
+            Self::Low => 8,
+            Self::Medium => 4,
+            Self::High => 2,
+            Self::Ultra => 1,
+        }
+    }
+
+    pub fn lower(&self) -> Option<Self> {
+        match self {
+            Self::Low => None,
+            Self::Medium => Some(Self::Low),
+            Self::High => Some(Self::Medium),
+            Self::Ultra => Some(Self::High),
+        }
+    }
+
+    pub fn higher(&self) -> Option<Self> {
+        match self {
+            Self::Low => Some(Self::Medium),
+            Self::Medium => Some(Self::High),
+            Self::High => Some(Self::Ultra),
+            Self::Ultra => None,
+        }
+    }
+}
+
+impl Display for Quality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Ultra => "ultra",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Player settings, every field is optional
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct PlayerSettings {
+    /// Tiles per second, default: 5
     pub player_movement_speed: f32,
+    /// Radians per second, default: 2
     pub player_rotation_speed: f32,
 }
 
-#[derive(Default, Deserialize)]
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            player_movement_speed: 5.0,
+            player_rotation_speed: 2.0,
+        }
+    }
+}
+
+/// Campaign settings, every field is optional
+#[derive(Default, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CampaignSettings {
+    /// Episode manifest with the list of levels, `[level]` section is played if not set
+    pub episode: Option<String>,
+}
+
+/// Minimap settings, every field is optional
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MinimapSettings {
+    /// Default: top_right
+    pub corner: MinimapCorner,
+    /// Frame size in pixels, default: 250
+    pub size: u32,
+    /// Distance to the screen edges in pixels, default: 10
+    pub margin: u32,
+    /// Keep player in the centre and scroll the map, default: true
+    pub player_centered: bool,
+    /// Rotate the map so the player always looks up, default: false
+    pub rotate: bool,
+    /// Draw field of view rays, default: true
+    pub show_rays: bool,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            corner: MinimapCorner::default(),
+            size: 250,
+            margin: 10,
+            player_centered: true,
+            rotate: false,
+            show_rays: true,
+        }
+    }
+}
+
+/// HUD settings, every field is optional
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HudSettings {
+    /// Show FPS and frame time, default: true
+    pub show_fps: bool,
+    /// Default: true
+    pub crosshair: bool,
+    /// Font scale factor, glyphs are 8x8 pixels, default: 2
+    pub scale: u32,
+    /// Time to show a message in seconds, default: 3
+    pub message_time: f32,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            show_fps: true,
+            crosshair: true,
+            scale: 2,
+            message_time: 3.0,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MinimapCorner {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct LevelInfo {
+    /// Shown on the intermission screen, default: map file name
+    pub name: Option<String>,
     pub map: String,
     pub player_x: f32,
     pub player_y: f32,
+    /// Radians or degrees ("90deg"), default: 0
+    #[serde(default, deserialize_with = "deserialize_angle")]
+    pub player_angle: f32,
+    /// Player's health on the level start, default: 100
+    #[serde(default = "default_health")]
+    pub health: i32,
+    /// Player's armor on the level start, default: 0
+    #[serde(default)]
+    pub armor: i32,
+    /// Actors, items and triggers placed on the map, `[[level.entities]]` tables
+    #[serde(default)]
+    pub entities: Vec<EntitySpawn>,
+    /// Floor areas hurting the player, `[[level.hazards]]` tables
+    #[serde(default)]
+    pub hazards: Vec<Hazard>,
+    /// Wall tiles opened with the use key, `[[level.doors]]` tables
+    #[serde(default)]
+    pub doors: Vec<Door>,
+    /// Secret wall tiles sliding away when used, `[[level.push_walls]]` tables
+    #[serde(default)]
+    pub push_walls: Vec<PushWall>,
+    /// Heights and transparency of the wall tiles by texture, `[[level.tiles]]` tables
+    #[serde(default)]
+    pub tiles: Vec<TileInfo>,
+    /// Linked thin wall tiles seen and walked through, `[[level.portals]]` tables
+    #[serde(default)]
+    pub portals: Vec<Portal>,
+    /// Events firing actions, `[[level.triggers]]` tables
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    /// Areas finishing the level, `[[level.exits]]` tables
+    #[serde(default)]
+    pub exits: Vec<Area>,
+    /// Hidden areas counted on the intermission screen, `[[level.secrets]]` tables
+    #[serde(default)]
+    pub secrets: Vec<Area>,
+}
+
+fn default_health() -> i32 {
+    100
+}
+
+impl LevelInfo {
+    /// Level name or the map file name if it isn't set
+    pub fn title(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            Path::new(&self.map)
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| self.map.clone())
+        })
+    }
+}
+
+/// Rectangle of tiles
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Area {
+    /// Top left tile, default: 0, 0
+    #[serde(default)]
+    pub col: usize,
+    #[serde(default)]
+    pub row: usize,
+    /// Size in tiles, default: 1
+    #[serde(default = "default_area_size")]
+    pub width: usize,
+    #[serde(default = "default_area_size")]
+    pub height: usize,
+}
+
+fn default_area_size() -> usize {
+    1
+}
+
+impl Area {
+    pub fn contains(&self, col: usize, row: usize) -> bool {
+        (self.col..self.col + self.width).contains(&col)
+            && (self.row..self.row + self.height).contains(&row)
+    }
+
+    /// Tiles of the area in row-major order
+    pub fn tiles(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.row..self.row + self.height)
+            .flat_map(move |row| (self.col..self.col + self.width).map(move |col| (col, row)))
+    }
+}
+
+/// Rectangular floor area which deals damage over time
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hazard {
+    #[serde(flatten)]
+    pub area: Area,
+    /// Health points per second
+    pub damage: f32,
+}
+
+/// Wall tile which disappears when the player uses it
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Door {
+    pub col: usize,
+    pub row: usize,
+    /// red, blue or yellow, the door is unlocked if not set
+    pub key: Option<KeyColor>,
+}
+
+/// Wall tile which slides away from the player when used and counts as a secret
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PushWall {
+    pub col: usize,
+    pub row: usize,
+    /// Number of tiles to slide, 1 or 2, default: 2
+    #[serde(default = "default_push_distance")]
+    pub distance: usize,
+}
+
+/// Pair of thin wall tiles, rays and the player passing one of them come out of the other
+/// at the same spot along the wall, both ways
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Portal {
+    pub col: usize,
+    pub row: usize,
+    pub target_col: usize,
+    pub target_row: usize,
+}
+
+fn default_push_distance() -> usize {
+    2
+}
+
+/// Properties of the wall tiles with the texture, heights are in wall units
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TileInfo {
+    /// Wall texture of the map
+    pub tile: i32,
+    /// default: 1
+    #[serde(default = "default_tile_height")]
+    pub height: f32,
+    /// Elevation of the wall's bottom, stacked tiles float above the floor, default: 0
+    #[serde(default)]
+    pub floor: f32,
+    /// Thin or diagonal wall inside of the cell, default: block
+    #[serde(default)]
+    pub shape: TileShape,
+    /// Texture has transparent parts, walls behind it are visible, default: false
+    #[serde(default)]
+    pub masked: bool,
+    /// Blocks movement of the player and enemies, default: true
+    #[serde(default = "default_true")]
+    pub solid: bool,
+    /// Stops bullets and projectiles, default: true
+    #[serde(default = "default_true")]
+    pub blocks_shots: bool,
+    /// Reflects the view, the texture shows once the bounce limit is reached, default: false
+    #[serde(default)]
+    pub mirror: bool,
+    /// Color multiplying the reflection, e.g. [160, 170, 200] fades it to blue,
+    /// default: [255, 255, 255]
+    #[serde(default = "default_tint")]
+    pub tint: (u8, u8, u8),
+}
+
+fn default_tile_height() -> f32 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_tint() -> (u8, u8, u8) {
+    (255, 255, 255)
+}
+
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntitySpawn {
+    /// enemy, item, decoration or trigger
+    pub kind: EntityKind,
+    pub x: f32,
+    pub y: f32,
+    /// Radians or degrees ("90deg"), default: 0
+    #[serde(default, deserialize_with = "deserialize_angle")]
+    pub angle: f32,
+    /// Referenced by kill triggers
+    pub name: Option<String>,
+    /// Text shown when the player touches the entity
+    pub message: Option<String>,
+    /// Item type, default: treasure.
+    /// health, armor, ammo, rockets, red_key, blue_key, yellow_key or treasure
+    pub item: Option<ItemKind>,
+    /// Health, armor, ammo or score given by the item, default depends on the item type
+    pub amount: Option<i32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "
+[level]
+map = \"assets/map.pbm\"
+player_x = 1.5
+player_y = 1.5
+";
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Writes the settings file for the test and loads it with the given environment,
+    /// the process environment isn't read
+    fn load_with_env(
+        name: &str,
+        content: &str,
+        vars: &[(&str, &str)],
+        overrides: &[(&str, &str)],
+    ) -> Result<Settings, SettingsError> {
+        let path = env::temp_dir().join(format!("raycaster-settings-{name}.toml"));
+        fs::write(&path, content).unwrap();
+        let env = env_overrides(pairs(vars).into_iter());
+        let settings = Settings::with_sources(&path, &env, &pairs(overrides));
+        fs::remove_file(&path).unwrap();
+        settings
+    }
+
+    fn load(
+        name: &str,
+        content: &str,
+        overrides: &[(&str, &str)],
+    ) -> Result<Settings, SettingsError> {
+        load_with_env(name, content, &[], overrides)
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let content = format!("[scene]\nfps_limit = 30\n{LEVEL}");
+        let err = load("unknown-scene", &content, &[]).err().unwrap();
+        assert!(err.reason.contains("unknown field `fps_limit`"), "{err}");
+        let content = format!("{LEVEL}spawn_x = 2.0\n");
+        let err = load("unknown-level", &content, &[]).err().unwrap();
+        assert!(err.reason.contains("unknown field `spawn_x`"), "{err}");
+        let err = load("unknown-section", LEVEL, &[("sound.volume", "1")])
+            .err()
+            .unwrap();
+        assert!(err.reason.contains("unknown field `sound`"), "{err}");
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let content = format!("[scene]\nfps = 60\nmax_depth = 20\n{LEVEL}");
+        let settings = load("file", &content, &[]).unwrap();
+        assert_eq!(settings.scene.fps, 60);
+        let overrides = [("scene.fps", "30"), ("scene.fps", "45")];
+        let settings = load("overrides", &content, &overrides).unwrap();
+        assert_eq!(settings.scene.fps, 45);
+        let vars = [("RAYCASTER_SCENE__MAX_DEPTH", "30"), ("HOME", "/root")];
+        let settings = load_with_env("env", &content, &vars, &[]).unwrap();
+        assert_eq!(settings.scene.max_depth, 30);
+        let overrides = [("scene.max_depth", "40")];
+        let settings = load_with_env("env-overrides", &content, &vars, &overrides).unwrap();
+        assert_eq!(settings.scene.max_depth, 40);
+    }
+
+    #[test]
+    fn values_out_of_range_are_rejected() {
+        assert!(check_range("scene.fps", 1, 1, 1000).is_ok());
+        assert!(check_range("scene.fps", 1000, 1, 1000).is_ok());
+        let (key, reason) = check_range("scene.fps", 0, 1, 1000).unwrap_err();
+        assert_eq!(key, "scene.fps");
+        assert_eq!(reason, "must be in range 1..=1000, got 0");
+        assert!(check_range("hud.message_time", -0.5, 0.0, 3600.0).is_err());
+        let err = load("range", LEVEL, &[("scene.max_portals", "17")])
+            .err()
+            .unwrap();
+        assert_eq!(err.key.as_deref(), Some("scene.max_portals"));
+    }
 }
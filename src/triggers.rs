@@ -0,0 +1,287 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    common::Float,
+    settings::{Area, EntitySpawn},
+};
+
+/// What makes a trigger fire
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerEvent {
+    /// The player steps into the area
+    Enter,
+    /// The player uses the tile at `col` and `row`, e.g. a switch on a wall
+    Use,
+    /// The named `entity` is killed, or the last enemy if no name is given
+    Kill,
+    /// `time` seconds after the level start
+    Timer,
+}
+
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TriggerAction {
+    /// Removes the wall tile ignoring the door's key
+    OpenDoor {
+        col: usize,
+        row: usize,
+    },
+    /// Changes the tile's texture, 0 makes it empty
+    SetTile {
+        col: usize,
+        row: usize,
+        tile: i32,
+    },
+    Spawn(EntitySpawn),
+    Message {
+        text: String,
+    },
+    /// Sounds are logged, there is no audio output yet
+    Sound {
+        name: String,
+    },
+    EndLevel,
+}
+
+/// Level event with a list of actions, `[[level.triggers]]` tables
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trigger {
+    pub event: TriggerEvent,
+    /// Enter area, its top left tile is the used tile
+    #[serde(flatten)]
+    pub area: Area,
+    /// Entity name for kill triggers
+    pub entity: Option<String>,
+    /// Seconds for timers
+    pub time: Option<f32>,
+    /// Fire only once, repeating timers fire every `time` seconds, default: true
+    #[serde(default = "default_once")]
+    pub once: bool,
+    pub actions: Vec<TriggerAction>,
+}
+
+fn default_once() -> bool {
+    true
+}
+
+/// Things happened during the frame
+pub struct TriggerContext<'a> {
+    pub player_tile: (usize, usize),
+    pub used_tile: Option<(usize, usize)>,
+    /// names of the enemies killed during the frame, `None` for unnamed ones
+    pub killed: &'a [Option<String>],
+    pub enemies_left: usize,
+    /// level time before and after the frame
+    pub previous_time: Float,
+    pub level_time: Float,
+}
+
+#[derive(Default, Clone)]
+struct TriggerState {
+    is_fired: bool,
+    /// the player was inside of the enter area on the previous frame
+    is_inside: bool,
+}
+
+/// Runtime state of the level triggers
+#[derive(Default)]
+pub struct Triggers {
+    states: Vec<TriggerState>,
+}
+
+impl Triggers {
+    pub fn new(count: usize) -> Self {
+        Self {
+            states: vec![TriggerState::default(); count],
+        }
+    }
+
+    /// Indices of the triggers which won't fire anymore
+    pub fn fired(&self) -> Vec<usize> {
+        self.states
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| state.is_fired)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn set_fired(&mut self, fired: &[usize]) {
+        for i in fired {
+            if let Some(state) = self.states.get_mut(*i) {
+                state.is_fired = true;
+            }
+        }
+    }
+
+    /// Returns actions of the triggers fired during the frame in the level order
+    pub fn update(&mut self, triggers: &[Trigger], context: &TriggerContext) -> Vec<TriggerAction> {
+        if self.states.len() != triggers.len() {
+            self.states.resize(triggers.len(), TriggerState::default());
+        }
+        let mut actions = Vec::new();
+        for (trigger, state) in triggers.iter().zip(self.states.iter_mut()) {
+            let (col, row) = context.player_tile;
+            let is_inside = trigger.area.contains(col, row);
+            let was_inside = std::mem::replace(&mut state.is_inside, is_inside);
+            if state.is_fired {
+                continue;
+            }
+            let is_fired = match trigger.event {
+                TriggerEvent::Enter => is_inside && !was_inside,
+                TriggerEvent::Use => {
+                    context.used_tile == Some((trigger.area.col, trigger.area.row))
+                }
+                TriggerEvent::Kill => match &trigger.entity {
+                    Some(name) => context
+                        .killed
+                        .iter()
+                        .any(|killed| killed.as_ref() == Some(name)),
+                    None => !context.killed.is_empty() && context.enemies_left == 0,
+                },
+                TriggerEvent::Timer => {
+                    let time = trigger.time.unwrap_or_default();
+                    if trigger.once {
+                        context.previous_time < time && context.level_time >= time
+                    } else {
+                        // number of periods passed has changed
+                        (context.previous_time / time).floor() < (context.level_time / time).floor()
+                    }
+                }
+            };
+            if !is_fired {
+                continue;
+            }
+            state.is_fired = trigger.once;
+            actions.extend(trigger.actions.iter().cloned());
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(content: &str) -> Trigger {
+        let actions = "actions = [{ action = \"message\", text = \"fired\" }]";
+        toml::from_str(&format!("{content}\n{actions}")).unwrap()
+    }
+
+    /// Frame where nothing happens with the player standing on the tile (1, 1)
+    fn context() -> TriggerContext<'static> {
+        TriggerContext {
+            player_tile: (1, 1),
+            used_tile: None,
+            killed: &[],
+            enemies_left: 1,
+            previous_time: 0.0,
+            level_time: 0.0,
+        }
+    }
+
+    /// Runs the frame and returns the number of fired triggers
+    fn fire(triggers: &mut Triggers, list: &[Trigger], context: &TriggerContext) -> usize {
+        triggers
+            .update(list, context)
+            .iter()
+            .filter(|action| matches!(action, TriggerAction::Message { text } if text == "fired"))
+            .count()
+    }
+
+    #[test]
+    fn enter_triggers_fire_on_stepping_in() {
+        let list = [trigger(
+            "event = \"enter\"\ncol = 2\nrow = 1\nwidth = 2\nonce = false",
+        )];
+        let mut triggers = Triggers::new(list.len());
+        assert_eq!(fire(&mut triggers, &list, &context()), 0);
+        let inside = TriggerContext {
+            player_tile: (3, 1),
+            ..context()
+        };
+        assert_eq!(fire(&mut triggers, &list, &inside), 1);
+        // staying inside doesn't fire it again, entering once more does
+        assert_eq!(fire(&mut triggers, &list, &inside), 0);
+        assert_eq!(fire(&mut triggers, &list, &context()), 0);
+        assert_eq!(fire(&mut triggers, &list, &inside), 1);
+    }
+
+    #[test]
+    fn use_triggers_fire_on_their_tile() {
+        let list = [trigger("event = \"use\"\ncol = 4\nrow = 2")];
+        let mut triggers = Triggers::new(list.len());
+        let used = |tile| TriggerContext {
+            used_tile: Some(tile),
+            ..context()
+        };
+        assert_eq!(fire(&mut triggers, &list, &used((4, 3))), 0);
+        assert_eq!(fire(&mut triggers, &list, &used((4, 2))), 1);
+        assert_eq!(triggers.fired(), [0]);
+    }
+
+    #[test]
+    fn kill_triggers_fire_on_named_or_last_kills() {
+        let list = [
+            trigger("event = \"kill\"\nentity = \"guard\""),
+            trigger("event = \"kill\""),
+        ];
+        let mut triggers = Triggers::new(list.len());
+        let other = [Some("boss".to_string())];
+        let killed = TriggerContext {
+            killed: &other,
+            ..context()
+        };
+        assert_eq!(fire(&mut triggers, &list, &killed), 0);
+        let guard = [None, Some("guard".to_string())];
+        let killed = TriggerContext {
+            killed: &guard,
+            enemies_left: 0,
+            ..context()
+        };
+        assert_eq!(fire(&mut triggers, &list, &killed), 2);
+    }
+
+    #[test]
+    fn once_timers_fire_once() {
+        let list = [trigger("event = \"timer\"\ntime = 2.0")];
+        let mut triggers = Triggers::new(list.len());
+        let frame = |previous_time, level_time| TriggerContext {
+            previous_time,
+            level_time,
+            ..context()
+        };
+        assert_eq!(fire(&mut triggers, &list, &frame(1.0, 1.5)), 0);
+        assert_eq!(fire(&mut triggers, &list, &frame(1.5, 2.1)), 1);
+        assert_eq!(fire(&mut triggers, &list, &frame(3.5, 4.1)), 0);
+    }
+
+    #[test]
+    fn repeating_timers_fire_every_period() {
+        let list = [trigger("event = \"timer\"\ntime = 2.0\nonce = false")];
+        let mut triggers = Triggers::new(list.len());
+        let frame = |previous_time, level_time| TriggerContext {
+            previous_time,
+            level_time,
+            ..context()
+        };
+        assert_eq!(fire(&mut triggers, &list, &frame(1.5, 2.1)), 1);
+        assert_eq!(fire(&mut triggers, &list, &frame(2.1, 3.0)), 0);
+        assert_eq!(fire(&mut triggers, &list, &frame(3.9, 4.0)), 1);
+        assert!(triggers.fired().is_empty());
+    }
+
+    #[test]
+    fn fired_triggers_stay_fired() {
+        let list = [trigger("event = \"use\"\ncol = 4\nrow = 2")];
+        let mut triggers = Triggers::new(list.len());
+        triggers.set_fired(&[0]);
+        let used = TriggerContext {
+            used_tile: Some((4, 2)),
+            ..context()
+        };
+        assert_eq!(fire(&mut triggers, &list, &used), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use std::ops::{Add, AddAssign, Sub};
+use std::ops::{Add, AddAssign, Mul, Sub};
 
 #[derive(Default, Copy, Clone)]
 pub struct Vec2d<T> {
@@ -12,6 +12,20 @@ impl<T> Vec2d<T> {
     }
 }
 
+impl<T> Vec2d<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    pub fn dot(&self, rhs: &Self) -> T {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    /// Z component of the 3D cross product, positive if `rhs` is clockwise on the map
+    pub fn cross(&self, rhs: &Self) -> T {
+        self.x * rhs.y - self.y * rhs.x
+    }
+}
+
 impl<T> AddAssign<Self> for Vec2d<T>
 where
     T: Copy + AddAssign,
@@ -1,50 +1,504 @@
+use std::{
+    collections::{HashMap, HashSet},
+    f32::consts::PI,
+    fs,
+};
+
+use serde_derive::{Deserialize, Serialize};
+
 use crate::{
-    common::{DrawCommand, Float2d},
+    common::{Float, Float2d},
+    pathfinding::find_path,
     pbm::PBMImage,
+    sectors::SectorMap,
+    settings::{Area, LevelInfo, TileInfo},
+    triggers::{TriggerAction, TriggerEvent},
 };
 
+/// Seconds a sliding wall takes to move by one tile
+const SLIDE_TIME: Float = 0.5;
+/// Half of the thin and diagonal walls' thickness for collisions
+const HALF_THICKNESS: Float = 0.1;
+
+/// Wall inside of the tile, thin walls don't fill the whole cell
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileShape {
+    #[default]
+    Block,
+    /// Thin walls along the cell edge
+    Top,
+    Bottom,
+    Left,
+    Right,
+    /// Thin walls through the cell centre
+    Horizontal,
+    Vertical,
+    /// From the top left corner to the bottom right one
+    Diagonal,
+    /// From the bottom left corner to the top right one
+    AntiDiagonal,
+}
+
+impl TileShape {
+    /// Ends of the thin wall relative to the top left corner of the tile
+    fn segment(self) -> Option<(Float2d, Float2d)> {
+        let (x1, y1, x2, y2) = match self {
+            TileShape::Block => return None,
+            TileShape::Top => (0.0, 0.0, 1.0, 0.0),
+            TileShape::Bottom => (0.0, 1.0, 1.0, 1.0),
+            TileShape::Left => (0.0, 0.0, 0.0, 1.0),
+            TileShape::Right => (1.0, 0.0, 1.0, 1.0),
+            TileShape::Horizontal => (0.0, 0.5, 1.0, 0.5),
+            TileShape::Vertical => (0.5, 0.0, 0.5, 1.0),
+            TileShape::Diagonal => (0.0, 0.0, 1.0, 1.0),
+            TileShape::AntiDiagonal => (0.0, 1.0, 1.0, 0.0),
+        };
+        Some((Float2d::new(x1, y1), Float2d::new(x2, y2)))
+    }
+}
+
+/// Wall tile sliding to the neighbour cells, the map content changes every whole tile moved
+struct SlidingTile {
+    col: usize,
+    row: usize,
+    direction: (i32, i32),
+    tiles_left: usize,
+    /// time spent moving to the next tile
+    time: Float,
+}
+
+impl SlidingTile {
+    /// Tile the wall moves into
+    fn next(&self) -> (i32, i32) {
+        (
+            self.col as i32 + self.direction.0,
+            self.row as i32 + self.direction.1,
+        )
+    }
+
+    /// Checks if the wall is between its tile and the next one
+    fn is_moving(&self) -> bool {
+        self.time > 0.0
+    }
+}
+
+/// Where a ray enters a sliding wall
+pub struct SlidingHit {
+    pub texture_id: i32,
+    pub depth: Float,
+    pub exit_depth: Float,
+    /// texture offset
+    pub offset: Float,
+    /// the ray hits a side facing along the x axis
+    pub is_vertical: bool,
+}
+
 #[derive(Default)]
 pub struct Walls {
     content: Vec<Vec<i32>>,
-    tile_size: usize,
+    sliding: Vec<SlidingTile>,
+    /// properties of the tiles by texture, ordinary walls aren't listed
+    tiles: HashMap<i32, TileInfo>,
+    max_height: Float,
+    /// sector geometry if the map is a sector file, `content` approximates it then
+    sectors: Option<SectorMap>,
+    /// linked portal tiles in both directions
+    portals: HashMap<(i32, i32), (i32, i32)>,
+    /// incremented on every map change so caches can tell they are outdated
+    revision: u64,
+    /// content of the map file, tells if the file changed since it was loaded
+    source: Vec<u8>,
 }
 
 impl Walls {
-    pub fn new(tile_size: usize) -> Self {
-        Self {
-            tile_size,
-            ..Default::default()
+    /// Loads the level's map, a PBM grid or a TOML sector file
+    pub fn prepare(&mut self, level_info: &LevelInfo) -> Result<(), String> {
+        let level_path = &level_info.map;
+        self.sectors = None;
+        let content = if level_path.ends_with(".toml") {
+            let sectors =
+                SectorMap::load(level_path).map_err(|err| format!("{level_path}: {err}"))?;
+            let content = sectors.rasterize();
+            self.sectors = Some(sectors);
+            content
+        } else {
+            let pbm_image =
+                PBMImage::with_file(level_path).map_err(|err| format!("{level_path}: {err}"))?;
+            pbm_image.transform_to_array(|x| x as i32)
+        };
+        if content.is_empty() || content[0].is_empty() {
+            return Err(format!("{level_path}: map is empty"));
+        }
+        self.source = fs::read(level_path).map_err(|err| format!("{level_path}: {err}"))?;
+        self.content = content;
+        self.revision += 1;
+        self.sliding.clear();
+        self.tiles = level_info
+            .tiles
+            .iter()
+            .map(|tile| (tile.tile, tile.clone()))
+            .collect();
+        self.max_height = self.tiles.values().fold(1.0, |max_height, tile| {
+            max_height.max(tile.floor + tile.height)
+        });
+        self.portals = level_info
+            .portals
+            .iter()
+            .flat_map(|portal| {
+                let from = (portal.col as i32, portal.row as i32);
+                let to = (portal.target_col as i32, portal.target_row as i32);
+                [(from, to), (to, from)]
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Checks if both maps are loaded from the same file content
+    pub fn has_same_source(&self, other: &Walls) -> bool {
+        self.source == other.source
+    }
+
+    /// Returns map size as (columns, rows)
+    pub fn size(&self) -> (usize, usize) {
+        let cols = self
+            .content
+            .first()
+            .map(|row| row.len())
+            .unwrap_or_default();
+        (cols, self.content.len())
+    }
+
+    pub fn tile(&self, col: usize, row: usize) -> i32 {
+        self.content[row][col]
+    }
+
+    /// Sector geometry of the level if its map is a sector file
+    pub fn sectors(&self) -> Option<&SectorMap> {
+        self.sectors.as_ref()
+    }
+
+    /// Height of the floor under the point, always 0 on grid maps
+    pub fn floor_height(&self, point: Float2d) -> Float {
+        self.sectors
+            .as_ref()
+            .map_or(0.0, |sectors| sectors.floor_height(point))
+    }
+
+    /// Checks that steps and ceilings between the points don't stop the player
+    pub fn can_move(&self, from: Float2d, to: Float2d) -> bool {
+        self.sectors
+            .as_ref()
+            .is_none_or(|sectors| sectors.can_move(from, to))
+    }
+
+    /// Returns the tile value or `None` if the tile is out of the map
+    pub fn checked_tile(&self, col: i32, row: i32) -> Option<i32> {
+        if col < 0 || row < 0 {
+            return None;
         }
+        self.content
+            .get(row as usize)
+            .and_then(|tiles| tiles.get(col as usize))
+            .copied()
+    }
+
+    /// Returns (floor, top) of the wall tile's texture in wall units
+    pub fn extent(&self, tile: i32) -> (Float, Float) {
+        self.tiles
+            .get(&tile)
+            .map_or((0.0, 1.0), |tile| (tile.floor, tile.floor + tile.height))
+    }
+
+    /// Checks if walls behind the tile's texture are visible
+    pub fn is_masked(&self, tile: i32) -> bool {
+        self.tiles.get(&tile).is_some_and(|tile| tile.masked)
+    }
+
+    /// Returns the tint of the reflection if the tile is a mirror
+    pub fn mirror(&self, tile: i32) -> Option<(u8, u8, u8)> {
+        self.tiles
+            .get(&tile)
+            .filter(|tile| tile.mirror)
+            .map(|tile| tile.tint)
+    }
+
+    fn is_solid(&self, tile: i32) -> bool {
+        tile > 0 && self.tiles.get(&tile).is_none_or(|tile| tile.solid)
+    }
+
+    fn shape(&self, tile: i32) -> TileShape {
+        self.tiles
+            .get(&tile)
+            .map(|tile| tile.shape)
+            .unwrap_or_default()
+    }
+
+    /// Returns ends of the thin or diagonal wall in the tile, `None` for empty
+    /// and whole cell walls
+    pub fn segment(&self, col: i32, row: i32) -> Option<(Float2d, Float2d)> {
+        let tile = self.checked_tile(col, row).filter(|tile| *tile > 0)?;
+        let (start, end) = self.shape(tile).segment()?;
+        let corner = Float2d::new(col as Float, row as Float);
+        Some((corner + start, corner + end))
+    }
+
+    /// Returns depth along the ray and texture offset where the ray crosses the thin wall
+    /// in the tile, `None` if it misses or the tile isn't a thin wall
+    pub fn intersect(
+        &self,
+        col: i32,
+        row: i32,
+        origin: Float2d,
+        direction: Float2d,
+    ) -> Option<(Float, Float)> {
+        let (start, end) = self.segment(col, row)?;
+        let wall = end - start;
+        let denominator = direction.cross(&wall);
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+        let to_start = start - origin;
+        let depth = to_start.cross(&wall) / denominator;
+        let offset = to_start.cross(&direction) / denominator;
+        if depth < 0.0 || !(0.0..=1.0).contains(&offset) {
+            return None;
+        }
+        // keep the texture readable from both sides
+        let offset = if denominator > 0.0 {
+            offset
+        } else {
+            1.0 - offset
+        };
+        Some((depth, offset))
+    }
+
+    /// Returns the point on the portal in the tile, the matching point on the paired portal
+    /// and the angle the view turns by, points near the portal map by the same rotation
+    pub fn portal(&self, col: i32, row: i32) -> Option<(Float2d, Float2d, Float)> {
+        let (target_col, target_row) = *self.portals.get(&(col, row))?;
+        let (start, end) = self.segment(col, row)?;
+        let (target_start, target_end) = self.segment(target_col, target_row)?;
+        let angle = |Float2d { x, y }: Float2d| y.atan2(x);
+        let turn = angle(target_end - target_start) - angle(end - start);
+        Some((start, target_start, turn.rem_euclid(2.0 * PI)))
+    }
+
+    /// Returns the new position and the turn angle if the move crosses a portal
+    pub fn teleport(&self, from: Float2d, to: Float2d) -> Option<(Float2d, Float)> {
+        let (col, row) = (from.x.floor() as i32, from.y.floor() as i32);
+        let direction = to - from;
+        // the move is shorter than a tile, so the portal is in a neighbour tile
+        let (col, row) = (row - 1..=row + 1)
+            .flat_map(|row| (col - 1..=col + 1).map(move |col| (col, row)))
+            .filter(|tile| self.portals.contains_key(tile))
+            .find(|(col, row)| {
+                self.intersect(*col, *row, from, direction)
+                    .is_some_and(|(depth, _)| depth <= 1.0)
+            })?;
+        let (start, target, turn) = self.portal(col, row)?;
+        Some((target + rotate(to - start, turn), turn))
+    }
+
+    /// Checks if the tile is inside of the map and doesn't block movement,
+    /// thin walls block the whole tile for path finding
+    pub fn is_passable(&self, col: i32, row: i32) -> bool {
+        self.checked_tile(col, row)
+            .is_some_and(|tile| !self.is_solid(tile))
+    }
+
+    /// Checks if the tile is out of the map or stops shots
+    pub fn blocks_shots(&self, col: i32, row: i32) -> bool {
+        self.checked_tile(col, row).is_none_or(|tile| {
+            tile > 0 && self.tiles.get(&tile).is_none_or(|tile| tile.blocks_shots)
+        })
+    }
+
+    /// Checks if the tile is out of the map or can't be seen through
+    pub fn blocks_sight(&self, col: i32, row: i32) -> bool {
+        self.checked_tile(col, row)
+            .is_none_or(|tile| tile > 0 && !self.is_masked(tile))
+    }
+
+    /// Top of the highest wall on the map, at least 1
+    pub fn max_height(&self) -> Float {
+        self.max_height
+    }
+
+    /// Turns the tile into an empty one, e.g. an opened door
+    pub fn clear_tile(&mut self, col: usize, row: usize) {
+        self.set_tile(col, row, 0);
+    }
+
+    /// Changes the tile's texture, 0 makes it empty. Tiles out of the map are ignored
+    pub fn set_tile(&mut self, col: usize, row: usize, value: i32) {
+        if let Some(tile) = self
+            .content
+            .get_mut(row)
+            .and_then(|tiles| tiles.get_mut(col))
+        {
+            *tile = value;
+            self.revision += 1;
+        }
+    }
+
+    /// Changes whenever the map content does
+    pub fn revision(&self) -> u64 {
+        self.revision
     }
 
-    pub fn prepare(&mut self, level_path: &str) {
-        // TODO: refactor this method to return Result<...>
-        if let Ok(pbm_image) = PBMImage::with_file(level_path) {
-            self.content = pbm_image.transform_to_array(|x| x as i32);
+    /// Starts sliding the wall tile by `distance` tiles in `direction`.
+    /// Returns false if it isn't a wall or there is no room to slide
+    pub fn push_tile(
+        &mut self,
+        col: usize,
+        row: usize,
+        direction: (i32, i32),
+        distance: usize,
+    ) -> bool {
+        let is_wall = self.content.get(row).and_then(|tiles| tiles.get(col)) > Some(&0);
+        let is_sliding = self
+            .sliding
+            .iter()
+            .any(|tile| (tile.col, tile.row) == (col, row));
+        let has_room = (1..=distance as i32)
+            .all(|i| self.is_open(col as i32 + direction.0 * i, row as i32 + direction.1 * i));
+        if !is_wall || is_sliding || !has_room {
+            return false;
         }
+        self.sliding.push(SlidingTile {
+            col,
+            row,
+            direction,
+            tiles_left: distance,
+            time: 0.0,
+        });
+        true
     }
 
-    pub fn draw(&self, commands: &mut Vec<DrawCommand>) {
-        commands.push(DrawCommand::ColorRGB(255, 255, 255));
-        let tile_size = self.tile_size;
-        for (r, row) in self.content.iter().enumerate() {
-            for (c, val) in row.iter().enumerate() {
-                if *val == 0 {
-                    continue;
+    /// Moves sliding walls, a wall waits while `is_occupied` tells the next tile is taken
+    /// by something and stops early if a wall took it
+    pub fn update(&mut self, elapsed: Float, is_occupied: impl Fn(i32, i32) -> bool) {
+        let mut sliding = std::mem::take(&mut self.sliding);
+        sliding.retain_mut(|tile| {
+            let mut elapsed = elapsed;
+            while tile.tiles_left > 0 {
+                let (col, row) = tile.next();
+                if !self.is_open(col, row) {
+                    return false;
+                }
+                if !tile.is_moving() && is_occupied(col, row) {
+                    return true;
+                }
+                tile.time += elapsed;
+                if tile.time < SLIDE_TIME {
+                    return true;
                 }
-                let obj = DrawCommand::Rectangle {
-                    x: (c * tile_size) as i32,
-                    y: (r * tile_size) as i32,
-                    w: tile_size as u32,
-                    h: tile_size as u32,
-                    fill: true,
+                elapsed = tile.time - SLIDE_TIME;
+                tile.time = 0.0;
+                let value = self.tile(tile.col, tile.row);
+                self.clear_tile(tile.col, tile.row);
+                (tile.col, tile.row) = (col as usize, row as usize);
+                self.set_tile(tile.col, tile.row, value);
+                tile.tiles_left -= 1;
+            }
+            false
+        });
+        self.sliding = sliding;
+    }
+
+    /// Returns `None` if no wall is sliding through the tile, otherwise where the ray
+    /// enters the wall if it happens in this tile
+    pub fn intersect_sliding(
+        &self,
+        col: i32,
+        row: i32,
+        origin: Float2d,
+        direction: Float2d,
+    ) -> Option<Option<SlidingHit>> {
+        let tile = self.sliding.iter().find(|tile| {
+            tile.is_moving()
+                && ((tile.col as i32, tile.row as i32) == (col, row) || tile.next() == (col, row))
+        })?;
+        let shift = tile.time / SLIDE_TIME;
+        let corner = Float2d::new(
+            tile.col as Float + shift * tile.direction.0 as Float,
+            tile.row as Float + shift * tile.direction.1 as Float,
+        );
+        // depths where the ray enters and leaves the wall's span along an axis
+        let span = |start: Float, origin: Float, direction: Float| {
+            if direction.abs() < 1e-6 {
+                return if (start..=start + 1.0).contains(&origin) {
+                    (Float::MIN, Float::MAX)
+                } else {
+                    (Float::MAX, Float::MIN)
                 };
-                commands.push(obj);
             }
+            let (near, far) = (
+                (start - origin) / direction,
+                (start + 1.0 - origin) / direction,
+            );
+            (near.min(far), near.max(far))
+        };
+        let (x_enter, x_exit) = span(corner.x, origin.x, direction.x);
+        let (y_enter, y_exit) = span(corner.y, origin.y, direction.y);
+        let (depth, exit_depth) = (x_enter.max(y_enter), x_exit.min(y_exit));
+        if depth < 0.0 || depth > exit_depth {
+            return Some(None);
+        }
+        // the wall covers two tiles, it is hit from the one the ray enters it in
+        let inside =
+            origin + Float2d::new((depth + 1e-4) * direction.x, (depth + 1e-4) * direction.y);
+        if (inside.x.floor() as i32, inside.y.floor() as i32) != (col, row) {
+            return Some(None);
+        }
+        let hit = origin + Float2d::new(depth * direction.x, depth * direction.y) - corner;
+        let is_vertical = x_enter > y_enter;
+        let offset = match (is_vertical, direction.x > 0.0, direction.y > 0.0) {
+            (true, true, _) => hit.y,
+            (true, false, _) => 1.0 - hit.y,
+            (false, _, true) => 1.0 - hit.x,
+            (false, _, false) => hit.x,
+        };
+        Some(Some(SlidingHit {
+            texture_id: self.tile(tile.col, tile.row),
+            depth,
+            exit_depth,
+            offset: offset.clamp(0.0, 1.0),
+            is_vertical,
+        }))
+    }
+
+    /// Returns (column, row, value) of the tiles which differ from the `original` map,
+    /// sliding walls are taken at their destinations
+    pub fn changed_tiles(&self, original: &Walls) -> Vec<(usize, usize, i32)> {
+        let mut content = self.content.clone();
+        for tile in &self.sliding {
+            let value = content[tile.row][tile.col];
+            content[tile.row][tile.col] = 0;
+            let col = tile.col as i32 + tile.direction.0 * tile.tiles_left as i32;
+            let row = tile.row as i32 + tile.direction.1 * tile.tiles_left as i32;
+            content[row as usize][col as usize] = value;
         }
+        content
+            .iter()
+            .zip(&original.content)
+            .enumerate()
+            .flat_map(|(row, (tiles, original))| {
+                tiles
+                    .iter()
+                    .zip(original)
+                    .enumerate()
+                    .filter(|(_, (tile, original))| tile != original)
+                    .map(move |(col, (tile, _))| (col, row, *tile))
+            })
+            .collect()
     }
 
     pub fn has_collision(&self, point: Float2d) -> bool {
+        if let Some(sectors) = &self.sectors {
+            return sectors.has_collision(point);
+        }
         let Float2d { x, y } = point;
         if x < 0.0 || y < 0.0 {
             // println!("[WARN]: map location is out of bounds x:{x:.2}, y:{y:.2} (1)");
@@ -55,12 +509,367 @@ impl Walls {
             // println!("[WARN]: map location is out of bounds x:{x:.2}, y:{y:.2} (2)");
             return false;
         }
-        self.content[row][col] > 0
+        let tile = self.content[row][col];
+        if self.is_solid(tile) && self.shape(tile) == TileShape::Block {
+            return true;
+        }
+        // a wall sliding into the tile already takes it
+        let is_taken = self
+            .sliding
+            .iter()
+            .any(|tile| tile.is_moving() && tile.next() == (col as i32, row as i32));
+        if is_taken {
+            return true;
+        }
+        // thin walls of the neighbour tiles may lie on the tile's edges
+        let (col, row) = (col as i32, row as i32);
+        (row - 1..=row + 1).any(|row| {
+            (col - 1..=col + 1).any(|col| {
+                // the player walks through portals
+                let is_solid = !self.portals.contains_key(&(col, row))
+                    && self
+                        .checked_tile(col, row)
+                        .is_some_and(|tile| self.is_solid(tile));
+                is_solid
+                    && self
+                        .segment(col, row)
+                        .is_some_and(|segment| distance(point, segment) < HALF_THICKNESS)
+            })
+        })
     }
 
-    pub fn texture_id(&self, point: Float2d) -> i32 {
-        // no range check!!
-        let (col, row) = (point.x as usize, point.y as usize);
-        self.content[row][col]
+    /// Checks if point is inside of the map and isn't occupied by a solid wall
+    pub fn is_walkable(&self, point: Float2d) -> bool {
+        let Float2d { x, y } = point;
+        x >= 0.0
+            && y >= 0.0
+            && self.checked_tile(x as i32, y as i32).is_some()
+            && !self.has_collision(point)
+    }
+
+    /// Checks if the tile is inside of the map and is empty
+    pub fn is_open(&self, col: i32, row: i32) -> bool {
+        self.checked_tile(col, row) == Some(0)
+    }
+
+    /// Returns center of the first empty tile in row-major order
+    pub fn first_walkable(&self) -> Option<Float2d> {
+        self.content.iter().enumerate().find_map(|(row, tiles)| {
+            tiles
+                .iter()
+                .position(|tile| *tile == 0)
+                .map(|col| Float2d::new(col as Float + 0.5, row as Float + 0.5))
+        })
+    }
+
+    /// Checks that every border tile is a wall, so rays and player can't leave the map
+    pub fn is_enclosed(&self) -> bool {
+        let Some(last_row) = self.content.len().checked_sub(1) else {
+            return false;
+        };
+        self.content.iter().enumerate().all(|(r, row)| {
+            row.iter()
+                .enumerate()
+                .all(|(c, val)| *val > 0 || (r > 0 && r < last_row && c > 0 && c + 1 < row.len()))
+        })
+    }
+}
+
+#[cfg(test)]
+impl Walls {
+    /// Map from rows of tile digits with the tiles' properties
+    pub fn with_rows(rows: &[&str], tiles: &[TileInfo]) -> Self {
+        let content = rows
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|tile| tile.to_digit(10).unwrap() as i32)
+                    .collect()
+            })
+            .collect();
+        Self {
+            content,
+            tiles: tiles.iter().map(|tile| (tile.tile, tile.clone())).collect(),
+            max_height: 1.0,
+            ..Self::default()
+        }
+    }
+}
+
+/// Loads the level's map and checks the level against it, returns found problems and prints warnings.
+/// `file` is the file the level is described in
+pub fn validate_level(level_info: &LevelInfo, file: &str) -> Result<Vec<String>, String> {
+    let mut walls = Walls::default();
+    walls.prepare(level_info)?;
+    let mut errors = Vec::new();
+    if !walls.is_enclosed() {
+        errors.push(format!("{}: map border has open tiles", level_info.map));
+    }
+    if !walls.is_walkable(Float2d::new(level_info.player_x, level_info.player_y)) {
+        errors.push(format!(
+            "{}: player spawn point ({}, {}) is inside of a wall or out of the map",
+            file, level_info.player_x, level_info.player_y
+        ));
+    }
+    let spawn_tile = (level_info.player_x as i32, level_info.player_y as i32);
+    for (i, spawn) in level_info.entities.iter().enumerate() {
+        if !walls.is_walkable(Float2d::new(spawn.x, spawn.y)) {
+            errors.push(format!(
+                "{}: entity #{i} ({:?}) at ({}, {}) is inside of a wall or out of the map",
+                file, spawn.kind, spawn.x, spawn.y
+            ));
+            continue;
+        }
+        // unreachable entities may be intentional, e.g. decorations behind windows
+        let tile = (spawn.x as i32, spawn.y as i32);
+        if find_path(&walls, spawn_tile, tile, usize::MAX).is_none() {
+            println!(
+                "[WARN] {}: entity #{i} ({:?}) at ({}, {}) can't be reached from the spawn point",
+                file, spawn.kind, spawn.x, spawn.y
+            );
+        }
+    }
+    for (i, door) in level_info.doors.iter().enumerate() {
+        let (cols, rows) = walls.size();
+        if door.col >= cols || door.row >= rows || walls.tile(door.col, door.row) == 0 {
+            errors.push(format!(
+                "{}: door #{i} at ({}, {}) isn't a wall tile",
+                file, door.col, door.row
+            ));
+            continue;
+        }
+        let has_key = |color| {
+            level_info
+                .entities
+                .iter()
+                .any(|spawn| spawn.item.and_then(|item| item.key_color()) == Some(color))
+        };
+        if let Some(color) = door.key.filter(|color| !has_key(*color)) {
+            println!(
+                "[WARN] {}: door #{i} at ({}, {}) needs the {color} key which isn't placed on the level",
+                file,
+                door.col,
+                door.row
+            );
+        }
+    }
+    for (i, push_wall) in level_info.push_walls.iter().enumerate() {
+        let (cols, rows) = walls.size();
+        if push_wall.col >= cols
+            || push_wall.row >= rows
+            || walls.tile(push_wall.col, push_wall.row) == 0
+        {
+            errors.push(format!(
+                "{}: push wall #{i} at ({}, {}) isn't a wall tile",
+                file, push_wall.col, push_wall.row
+            ));
+        }
+    }
+    let mut portal_tiles = HashSet::new();
+    for (i, portal) in level_info.portals.iter().enumerate() {
+        let length = |col: usize, row: usize| {
+            let (start, end) = walls.segment(col as i32, row as i32)?;
+            let Float2d { x, y } = end - start;
+            Some(x.hypot(y))
+        };
+        let ends = [
+            (portal.col, portal.row),
+            (portal.target_col, portal.target_row),
+        ];
+        if !ends.iter().all(|tile| portal_tiles.insert(*tile)) {
+            errors.push(format!(
+                "{}: portal #{i} at ({}, {}) links a tile already used by a portal",
+                file, portal.col, portal.row
+            ));
+        }
+        let (Some(length), Some(target_length)) = (
+            length(portal.col, portal.row),
+            length(portal.target_col, portal.target_row),
+        ) else {
+            errors.push(format!(
+                "{}: portal #{i} at ({}, {}) or its target isn't a thin wall tile",
+                file, portal.col, portal.row
+            ));
+            continue;
+        };
+        if (length - target_length).abs() > 1e-3 {
+            errors.push(format!(
+                "{}: portal #{i} at ({}, {}) links a diagonal wall with a straight one",
+                file, portal.col, portal.row
+            ));
+        }
+    }
+    for (i, trigger) in level_info.triggers.iter().enumerate() {
+        let (cols, rows) = walls.size();
+        let Area { col, row, .. } = trigger.area;
+        let is_wall = col < cols && row < rows && walls.tile(col, row) != 0;
+        if trigger.event == TriggerEvent::Use && !is_wall {
+            errors.push(format!(
+                "{file}: use trigger #{i} at ({col}, {row}) isn't a wall tile"
+            ));
+        }
+        let Some(name) = trigger.entity.as_ref() else {
+            continue;
+        };
+        let spawns = level_info.triggers.iter().flat_map(|trigger| {
+            trigger.actions.iter().filter_map(|action| match action {
+                TriggerAction::Spawn(spawn) => Some(spawn),
+                _ => None,
+            })
+        });
+        let is_known = level_info
+            .entities
+            .iter()
+            .chain(spawns)
+            .any(|spawn| spawn.name.as_ref() == Some(name));
+        if !is_known {
+            println!(
+                "[WARN] {}: trigger #{i} waits for entity '{name}' which isn't placed on the level",
+                file
+            );
+        }
+    }
+    for (kind, areas) in [("exit", &level_info.exits), ("secret", &level_info.secrets)] {
+        for (i, area) in areas.iter().enumerate() {
+            let blocked = area
+                .tiles()
+                .find(|(col, row)| !walls.is_open(*col as i32, *row as i32));
+            if let Some((col, row)) = blocked {
+                errors.push(format!(
+                    "{file}: {kind} #{i} at ({}, {}) covers a non-empty tile ({col}, {row})",
+                    area.col, area.row
+                ));
+            }
+        }
+    }
+    Ok(errors)
+}
+
+/// Distance from the point to the segment
+pub fn distance(point: Float2d, (start, end): (Float2d, Float2d)) -> Float {
+    let wall = end - start;
+    let t = ((point - start).dot(&wall) / wall.dot(&wall)).clamp(0.0, 1.0);
+    let Float2d { x, y } = point - (start + Float2d::new(wall.x * t, wall.y * t));
+    x.hypot(y)
+}
+
+/// Checks if the circle overlaps the tile
+pub fn overlaps_tile(center: Float2d, radius: Float, col: i32, row: i32) -> bool {
+    let (left, top) = (col as Float, row as Float);
+    let nearest = Float2d::new(
+        center.x.clamp(left, left + 1.0),
+        center.y.clamp(top, top + 1.0),
+    );
+    let Float2d { x, y } = center - nearest;
+    x.hypot(y) < radius
+}
+
+/// Rotates the vector by the angle, clockwise on the map for positive angles
+pub fn rotate(vector: Float2d, angle: Float) -> Float2d {
+    let (sin_a, cos_a) = angle.sin_cos();
+    Float2d::new(
+        vector.x * cos_a - vector.y * sin_a,
+        vector.x * sin_a + vector.y * cos_a,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Vertical thin wall in the middle of the map, tile 3 is a diagonal one
+    fn thin_walls() -> Walls {
+        let tile = |tile, shape| TileInfo {
+            shape,
+            ..toml::from_str(&format!("tile = {tile}")).unwrap()
+        };
+        Walls::with_rows(
+            &["1111", "1201", "1031", "1111"],
+            &[tile(2, TileShape::Vertical), tile(3, TileShape::Diagonal)],
+        )
+    }
+
+    fn assert_hit(hit: Option<(Float, Float)>, depth: Float, offset: Float) {
+        let (hit_depth, hit_offset) = hit.unwrap();
+        assert!((hit_depth - depth).abs() < 1e-5, "depth {hit_depth}");
+        assert!((hit_offset - offset).abs() < 1e-5, "offset {hit_offset}");
+    }
+
+    #[test]
+    fn rays_cross_thin_walls() {
+        let walls = thin_walls();
+        let right = Float2d::new(1.0, 0.0);
+        let left = Float2d::new(-1.0, 0.0);
+        let hit = walls.intersect(1, 1, Float2d::new(0.5, 1.25), right);
+        assert_hit(hit, 1.0, 0.25);
+        // the texture isn't mirrored on the back side
+        let hit = walls.intersect(1, 1, Float2d::new(2.5, 1.25), left);
+        assert_hit(hit, 1.0, 0.75);
+        let hit = walls.intersect(1, 1, Float2d::new(1.5, 1.25), right);
+        assert_hit(hit, 0.0, 0.25);
+        let direction = Float2d::new(0.5_f32.sqrt(), -(0.5_f32.sqrt()));
+        let hit = walls.intersect(2, 2, Float2d::new(2.0, 3.0), direction);
+        assert_hit(hit, 0.5_f32.sqrt(), 0.5);
+    }
+
+    #[test]
+    fn rays_miss_thin_walls() {
+        let walls = thin_walls();
+        let right = Float2d::new(1.0, 0.0);
+        // passes by the wall's end
+        assert!(walls
+            .intersect(1, 1, Float2d::new(0.5, 0.9), right)
+            .is_none());
+        // the wall is behind
+        assert!(walls
+            .intersect(1, 1, Float2d::new(2.5, 1.5), right)
+            .is_none());
+        // runs along the wall
+        let down = Float2d::new(0.0, 1.0);
+        assert!(walls
+            .intersect(1, 1, Float2d::new(1.5, 0.5), down)
+            .is_none());
+        // whole cell and empty tiles have no segments
+        assert!(walls
+            .intersect(0, 1, Float2d::new(0.5, 1.5), right)
+            .is_none());
+        assert!(walls
+            .intersect(2, 1, Float2d::new(0.5, 1.5), right)
+            .is_none());
+    }
+
+    #[test]
+    fn thin_walls_collide_near_the_segment() {
+        let walls = thin_walls();
+        assert!(walls.has_collision(Float2d::new(1.55, 1.5)));
+        assert!(!walls.has_collision(Float2d::new(1.2, 1.5)));
+        assert!(walls.has_collision(Float2d::new(2.5, 2.45)));
+        assert!(!walls.has_collision(Float2d::new(2.8, 2.2)));
+        let segment = (Float2d::new(0.0, 0.0), Float2d::new(2.0, 0.0));
+        assert_eq!(distance(Float2d::new(1.0, 0.5), segment), 0.5);
+        assert_eq!(distance(Float2d::new(3.0, 0.0), segment), 1.0);
+    }
+
+    #[test]
+    fn exits_and_secrets_are_checked_on_every_tile() {
+        let map = std::env::temp_dir().join("raycaster-walls-areas.pbm");
+        fs::write(
+            &map,
+            "P1\n5 4\n1 1 1 1 1\n1 0 1 0 1\n1 0 0 0 1\n1 1 1 1 1\n",
+        )
+        .unwrap();
+        let level_info: LevelInfo = toml::from_str(&format!(
+            "map = {:?}\nplayer_x = 1.5\nplayer_y = 2.5\n\
+             exits = [{{ col = 1, row = 1, width = 2 }}]\n\
+             secrets = [{{ col = 1, row = 2, width = 3 }}]",
+            map.display().to_string()
+        ))
+        .unwrap();
+        let errors = validate_level(&level_info, "level.toml");
+        fs::remove_file(&map).unwrap();
+        assert_eq!(
+            errors.unwrap(),
+            ["level.toml: exit #0 at (1, 1) covers a non-empty tile (2, 1)"]
+        );
     }
 }
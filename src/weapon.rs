@@ -0,0 +1,211 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    common::{DrawCommand, Float, ScreenSize},
+    control::ControllerState,
+};
+
+pub const TEXTURE_ID_PISTOL: i32 = 110;
+pub const TEXTURE_ID_LAUNCHER: i32 = 111;
+
+// weapon sprite size relative to the screen height
+const SPRITE_SIZE: Float = 0.4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeaponKind {
+    Pistol,
+    Launcher,
+}
+
+struct WeaponSpec {
+    name: &'static str,
+    /// animation frames are placed horizontally, the first one is idle
+    texture_id: i32,
+    frames: u32,
+    /// fill color if the texture is missing
+    color: (u8, u8, u8),
+    /// seconds between shots
+    fire_interval: Float,
+    damage: i32,
+    /// hitscan distance, projectile weapons ignore it
+    range: Float,
+    /// tiles per second, hitscan weapons have none
+    projectile_speed: Option<Float>,
+    initial_ammo: u32,
+    max_ammo: u32,
+}
+
+impl WeaponKind {
+    fn spec(&self) -> &'static WeaponSpec {
+        match self {
+            Self::Pistol => &WeaponSpec {
+                name: "Pistol",
+                texture_id: TEXTURE_ID_PISTOL,
+                frames: 4,
+                color: (110, 110, 120),
+                fire_interval: 0.35,
+                damage: 25,
+                range: 30.0,
+                projectile_speed: None,
+                initial_ammo: 50,
+                max_ammo: 200,
+            },
+            Self::Launcher => &WeaponSpec {
+                name: "Launcher",
+                texture_id: TEXTURE_ID_LAUNCHER,
+                frames: 4,
+                color: (80, 100, 80),
+                fire_interval: 0.9,
+                damage: 60,
+                range: 0.0,
+                projectile_speed: Some(8.0),
+                initial_ammo: 10,
+                max_ammo: 50,
+            },
+        }
+    }
+}
+
+pub enum Shot {
+    Hitscan { damage: i32, range: Float },
+    Projectile { damage: i32, speed: Float },
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Slot {
+    kind: WeaponKind,
+    ammo: u32,
+}
+
+/// Weapons carried by the player
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Weapons {
+    current: usize,
+    #[serde(skip)]
+    cooldown: Float,
+    // saved as an array of tables which must be the last field
+    slots: Vec<Slot>,
+}
+
+impl Default for Weapons {
+    fn default() -> Self {
+        let slots = [WeaponKind::Pistol, WeaponKind::Launcher]
+            .into_iter()
+            .map(|kind| Slot {
+                kind,
+                ammo: kind.spec().initial_ammo,
+            })
+            .collect();
+        Self {
+            current: 0,
+            cooldown: 0.0,
+            slots,
+        }
+    }
+}
+
+impl Weapons {
+    fn spec(&self) -> &'static WeaponSpec {
+        self.slots[self.current].kind.spec()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.spec().name
+    }
+
+    pub fn ammo(&self) -> u32 {
+        self.slots[self.current].ammo
+    }
+
+    /// Checks values read from a save file
+    pub fn validate(&self) -> Result<(), String> {
+        if self.current >= self.slots.len() {
+            return Err(format!("weapon slot {} doesn't exist", self.current));
+        }
+        Ok(())
+    }
+
+    /// Adds ammo up to the weapon's limit, returns false if it's full
+    pub fn add_ammo(&mut self, kind: WeaponKind, amount: u32) -> bool {
+        let Some(slot) = self.slots.iter_mut().find(|slot| slot.kind == kind) else {
+            return false;
+        };
+        let max_ammo = kind.spec().max_ammo;
+        if slot.ammo >= max_ammo {
+            return false;
+        }
+        slot.ammo = slot.ammo.saturating_add(amount).min(max_ammo);
+        true
+    }
+
+    /// Switches weapons and fires, returns the shot to be traced by the scene
+    pub fn update(
+        &mut self,
+        delta_time: Float,
+        controller_state: &ControllerState,
+    ) -> Option<Shot> {
+        self.cooldown = (self.cooldown - delta_time).max(0.0);
+        if let Some(slot) = controller_state.weapon_slot {
+            // can't switch in the middle of a shot
+            if slot < self.slots.len() && self.cooldown == 0.0 {
+                self.current = slot;
+            }
+        }
+        if !controller_state.fire_pressed || self.cooldown > 0.0 {
+            return None;
+        }
+        let slot = &mut self.slots[self.current];
+        if slot.ammo == 0 {
+            return None;
+        }
+        slot.ammo -= 1;
+        let spec = slot.kind.spec();
+        self.cooldown = spec.fire_interval;
+        let shot = match spec.projectile_speed {
+            Some(speed) => Shot::Projectile {
+                damage: spec.damage,
+                speed,
+            },
+            None => Shot::Hitscan {
+                damage: spec.damage,
+                range: spec.range,
+            },
+        };
+        Some(shot)
+    }
+
+    /// Animation frame, the shot animation lasts for the fire interval
+    fn frame(&self) -> u32 {
+        let spec = self.spec();
+        if self.cooldown == 0.0 {
+            return 0;
+        }
+        let progress = 1.0 - self.cooldown / spec.fire_interval;
+        1 + ((progress * (spec.frames - 1) as Float) as u32).min(spec.frames - 2)
+    }
+
+    pub fn draw(&self, commands: &mut Vec<DrawCommand>, screen_size: ScreenSize) {
+        let spec = self.spec();
+        let ScreenSize { width, height } = screen_size;
+        let size = (height as Float * SPRITE_SIZE) as u32;
+        let frame = self.frame();
+        // muzzle flash
+        let (r, g, b) = if frame == 1 {
+            (255, 220, 120)
+        } else {
+            spec.color
+        };
+        commands.push(DrawCommand::ColorRGB(r, g, b));
+        commands.push(DrawCommand::Sprite {
+            texture_id: spec.texture_id,
+            x: (width as i32 - size as i32) / 2,
+            y: (height - size) as i32,
+            width: size,
+            height: size,
+            src_x: frame as Float / spec.frames as Float,
+            src_width: 1.0 / spec.frames as Float,
+            shade: (255, 255, 255),
+        });
+    }
+}